@@ -0,0 +1,147 @@
+//! Aborting futures from the outside.
+
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use core::cell::{Cell, RefCell};
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+/// Wrap a future so it can be aborted through the returned [`AbortHandle`].
+///
+/// Once aborted, the next poll of the [`Abortable`] drops the wrapped future,
+/// deregistering any pollables it was waiting on from the reactor, and
+/// resolves to `Err(Aborted)`.
+pub fn abortable<F: Future>(future: F) -> (Abortable<F>, AbortHandle) {
+    let inner = Rc::new(Inner::default());
+    let handle = AbortHandle {
+        inner: inner.clone(),
+    };
+    let abortable = Abortable {
+        future: Some(Box::pin(future)),
+        inner,
+    };
+    (abortable, handle)
+}
+
+/// A future which can be aborted, created by [`abortable`].
+pub struct Abortable<F> {
+    future: Option<Pin<Box<F>>>,
+    inner: Rc<Inner>,
+}
+
+/// A handle to abort an [`Abortable`] future.
+///
+/// Clones of a handle all abort the same future.
+#[derive(Debug, Clone)]
+pub struct AbortHandle {
+    inner: Rc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    aborted: Cell<bool>,
+    waker: RefCell<Option<Waker>>,
+}
+
+impl AbortHandle {
+    /// Abort the future, waking the task which polls it.
+    ///
+    /// Aborting a future which already completed has no effect.
+    pub fn abort(&self) {
+        self.inner.aborted.set(true);
+        if let Some(waker) = self.inner.waker.borrow_mut().take() {
+            waker.wake();
+        }
+    }
+
+    /// Whether the future has been aborted.
+    pub fn is_aborted(&self) -> bool {
+        self.inner.aborted.get()
+    }
+}
+
+impl<F> Abortable<F> {
+    /// Whether the future has been aborted.
+    pub fn is_aborted(&self) -> bool {
+        self.inner.aborted.get()
+    }
+}
+
+impl<F: Future> Future for Abortable<F> {
+    type Output = Result<F::Output, Aborted>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if this.inner.aborted.get() {
+            this.future = None;
+            return Poll::Ready(Err(Aborted(())));
+        }
+
+        // Store the waker before polling, so an abort from within the future
+        // itself still wakes us up.
+        {
+            let mut waker = this.inner.waker.borrow_mut();
+            if !waker.as_ref().is_some_and(|w| w.will_wake(cx.waker())) {
+                *waker = Some(cx.waker().clone());
+            }
+        }
+        let future = this
+            .future
+            .as_mut()
+            .expect("`Abortable` polled after completion");
+        match future.as_mut().poll(cx) {
+            Poll::Ready(output) => {
+                this.future = None;
+                Poll::Ready(Ok(output))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<F> fmt::Debug for Abortable<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Abortable")
+            .field("aborted", &self.inner.aborted.get())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The error returned by an [`Abortable`] future once it has been aborted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Aborted(());
+
+impl fmt::Display for Aborted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the future was aborted")
+    }
+}
+
+impl core::error::Error for Aborted {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn abort_from_another_task() {
+        crate::block_on(|reactor| async move {
+            let (fut, handle) = abortable(core::future::pending::<()>());
+            let task = reactor.spawn(fut);
+            reactor.spawn(async move { handle.abort() }).await;
+            assert_eq!(task.await, Err(Aborted(())));
+        });
+    }
+
+    #[test]
+    fn completed_future_is_not_aborted() {
+        crate::block_on(|_| async {
+            let (fut, handle) = abortable(async { 1 });
+            assert_eq!(fut.await, Ok(1));
+            handle.abort();
+            assert!(handle.is_aborted());
+        });
+    }
+}
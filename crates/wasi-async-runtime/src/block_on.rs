@@ -1,53 +1,146 @@
+use super::task::{Task, TaskWaker};
 use super::Reactor;
 
+use alloc::vec::Vec;
 use core::future::Future;
 use core::pin::pin;
-use core::ptr;
 use core::task::Waker;
-use core::task::{Context, Poll, RawWaker, RawWakerVTable};
+use core::task::{Context, Poll};
 
 /// Start the event loop
+///
+/// The future is passed the reactor of the event loop. With the `std`
+/// feature, the reactor can also be obtained through
+/// [`Reactor::current`] while the event loop runs.
+///
+/// Once the future has resolved, tasks spawned onto the reactor keep being
+/// driven until they have all completed, so that background work such as
+/// flushing a writer isn't cut short. Tasks which can no longer make progress,
+/// because nothing they could be waiting on is registered with the reactor,
+/// are dropped. See [`block_on_abort`] to drop spawned tasks right away
+/// instead.
+///
+/// # Panics
+///
+/// Panics if the future is pending while no pollables are registered with
+/// the reactor, since nothing could wake it up again.
 pub fn block_on<F, Fut>(f: F) -> Fut::Output
+where
+    F: FnOnce(Reactor) -> Fut,
+    Fut: Future,
+{
+    run(f, true)
+}
+
+/// Start the event loop, dropping any spawned tasks which haven't completed
+/// once the future resolves.
+///
+/// Panics under the same conditions as [`block_on`].
+pub fn block_on_abort<F, Fut>(f: F) -> Fut::Output
+where
+    F: FnOnce(Reactor) -> Fut,
+    Fut: Future,
+{
+    run(f, false)
+}
+
+/// Run the event loop, draining spawned tasks after the future resolves if
+/// `drain` is set.
+fn run<F, Fut>(f: F, drain: bool) -> Fut::Output
 where
     F: FnOnce(Reactor) -> Fut,
     Fut: Future,
 {
     // Construct the reactor
     let reactor = Reactor::new();
+    #[cfg(feature = "std")]
+    let _guard = reactor.enter();
 
     // Create the future and pin it so it can be polled
     let fut = (f)(reactor.clone());
     let mut fut = pin!(fut);
 
     // Create a new context to be passed to the future.
-    let waker = noop_waker();
+    let root = TaskWaker::new();
+    let waker = Waker::from(root.clone());
     let mut cx = Context::from_waker(&waker);
 
+    // Tasks spawned onto the reactor, which we drive alongside the main future.
+    let mut tasks: Vec<Task> = Vec::new();
+
+    // The output of the main future, held on to while we drain the tasks.
+    let mut output = None;
+
     // Either the future completes and we return, or some IO is happening
     // and we wait.
     loop {
-        match fut.as_mut().poll(&mut cx) {
-            Poll::Ready(res) => return res,
-            Poll::Pending => reactor.block_until(),
+        if output.is_none() && root.take_woken() {
+            if let Poll::Ready(res) = fut.as_mut().poll(&mut cx) {
+                if !drain {
+                    return res;
+                }
+                output = Some(res);
+            }
+        }
+
+        tasks.append(&mut reactor.take_spawned());
+        tasks.retain_mut(|task| task.poll().is_pending());
+        let tasks_ready = tasks.iter().any(Task::is_woken) || reactor.has_spawned();
+
+        // Once the main future is done we're only waiting on the tasks. We're
+        // done once they have all completed, or if none of them are ready and
+        // there is no I/O left that could wake them up.
+        let idle = !tasks_ready && (tasks.is_empty() || !reactor.has_pollables());
+        if idle {
+            if let Some(res) = output.take() {
+                return res;
+            }
+        }
+
+        // Only block on IO once nothing is left that can make progress
+        // without it.
+        let ready = (output.is_none() && root.is_woken()) || tasks_ready;
+        if !ready {
+            reactor.block_until();
         }
     }
 }
 
-/// Construct a new no-op waker
-// NOTE: we can remove this once <https://github.com/rust-lang/rust/issues/98286> lands
-fn noop_waker() -> Waker {
-    const VTABLE: RawWakerVTable = RawWakerVTable::new(
-        // Cloning just returns a new no-op raw waker
-        |_| RAW,
-        // `wake` does nothing
-        |_| {},
-        // `wake_by_ref` does nothing
-        |_| {},
-        // Dropping does nothing as we don't allocate anything
-        |_| {},
-    );
-    const RAW: RawWaker = RawWaker::new(ptr::null(), &VTABLE);
-
-    // SAFETY: all fields are no-ops, so this is safe
-    unsafe { Waker::from_raw(RAW) }
+#[cfg(test)]
+mod tests {
+    use alloc::rc::Rc;
+    use core::cell::Cell;
+    use futures_lite::future::yield_now;
+
+    use super::*;
+
+    /// Spawn a task which only completes after yielding once, returning a
+    /// flag which is set once it completes.
+    fn spawn_slow_task(reactor: &Reactor) -> Rc<Cell<bool>> {
+        let done = Rc::new(Cell::new(false));
+        let flag = done.clone();
+        drop(reactor.spawn(async move {
+            yield_now().await;
+            flag.set(true);
+        }));
+        done
+    }
+
+    #[test]
+    fn block_on_drains_spawned_tasks() {
+        let done = block_on(|reactor| async move { spawn_slow_task(&reactor) });
+        assert!(done.get());
+    }
+
+    #[test]
+    fn block_on_abort_drops_spawned_tasks() {
+        let done = block_on_abort(|reactor| async move { spawn_slow_task(&reactor) });
+        assert!(!done.get());
+    }
+
+    #[test]
+    #[should_panic(expected = "runtime deadlock")]
+    fn pending_future_without_pollables_panics() {
+        block_on(|_| core::future::pending::<()>());
+    }
 }
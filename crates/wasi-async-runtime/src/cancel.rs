@@ -0,0 +1,91 @@
+//! Cooperative cancellation across tasks.
+
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use core::future;
+use core::task::{Poll, Waker};
+
+/// A token which can be used to signal cancellation to other tasks.
+///
+/// Clones of a token share their state: cancelling one cancels all of them,
+/// and wakes every task waiting on [`CancellationToken::cancelled`].
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Rc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: Cell<bool>,
+    wakers: RefCell<Vec<Waker>>,
+}
+
+impl CancellationToken {
+    /// Create a new, uncancelled token.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the token, waking all tasks waiting for it.
+    pub fn cancel(&self) {
+        self.inner.cancelled.set(true);
+        let wakers = core::mem::take(&mut *self.inner.wakers.borrow_mut());
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// Whether the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.get()
+    }
+
+    /// Wait until the token is cancelled.
+    pub async fn cancelled(&self) {
+        future::poll_fn(|cx| {
+            if self.is_cancelled() {
+                return Poll::Ready(());
+            }
+            let mut wakers = self.inner.wakers.borrow_mut();
+            if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                wakers.push(cx.waker().clone());
+            }
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancel_wakes_waiters() {
+        crate::block_on(|reactor| async move {
+            let token = CancellationToken::new();
+            let waiter = reactor.spawn({
+                let token = token.clone();
+                async move { token.cancelled().await }
+            });
+            let canceller = reactor.spawn({
+                let token = token.clone();
+                async move { token.cancel() }
+            });
+            canceller.await;
+            waiter.await;
+            assert!(token.is_cancelled());
+        });
+    }
+
+    #[test]
+    fn cancelled_resolves_once_cancelled() {
+        crate::block_on(|_| async {
+            let token = CancellationToken::new();
+            assert!(!token.is_cancelled());
+            token.cancel();
+            token.cancelled().await;
+        });
+    }
+}
@@ -0,0 +1,228 @@
+//! A bounded channel for sending values between tasks.
+
+use alloc::collections::VecDeque;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::fmt;
+use core::future;
+use core::task::{Poll, Waker};
+
+/// Create a bounded channel holding up to `capacity` values.
+///
+/// Sending waits while the channel is full, and receiving waits while it is
+/// empty. Since the runtime is single-threaded, the sender and receiver can't
+/// be sent across threads.
+///
+/// # Panics
+///
+/// Panics if `capacity` is `0`.
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "the capacity of a channel must be at least 1");
+    let inner = Rc::new(RefCell::new(Inner {
+        queue: VecDeque::with_capacity(capacity),
+        capacity,
+        senders: 1,
+        receiver_alive: true,
+        recv_waker: None,
+        send_wakers: Vec::new(),
+    }));
+    let sender = Sender {
+        inner: inner.clone(),
+    };
+    (sender, Receiver { inner })
+}
+
+#[derive(Debug)]
+struct Inner<T> {
+    queue: VecDeque<T>,
+    capacity: usize,
+    senders: usize,
+    receiver_alive: bool,
+    recv_waker: Option<Waker>,
+    send_wakers: Vec<Waker>,
+}
+
+impl<T> Inner<T> {
+    fn wake_receiver(&mut self) {
+        if let Some(waker) = self.recv_waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn wake_senders(&mut self) {
+        for waker in core::mem::take(&mut self.send_wakers) {
+            waker.wake();
+        }
+    }
+}
+
+/// The sending half of a [`channel`].
+///
+/// Senders can be cloned to send from several tasks. The channel closes once
+/// all senders are dropped.
+#[derive(Debug)]
+pub struct Sender<T> {
+    inner: Rc<RefCell<Inner<T>>>,
+}
+
+impl<T> Sender<T> {
+    /// Send a value, waiting until the channel has room for it.
+    ///
+    /// Fails, handing the value back, if the receiver has been dropped.
+    pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let mut value = Some(value);
+        future::poll_fn(|cx| {
+            let mut inner = self.inner.borrow_mut();
+            if !inner.receiver_alive {
+                // NOTE: the value is only taken when we resolve
+                return Poll::Ready(Err(SendError(value.take().unwrap())));
+            }
+            if inner.queue.len() < inner.capacity {
+                inner.queue.push_back(value.take().unwrap());
+                inner.wake_receiver();
+                return Poll::Ready(Ok(()));
+            }
+            if !inner.send_wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                inner.send_wakers.push(cx.waker().clone());
+            }
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Whether the receiver has been dropped.
+    pub fn is_closed(&self) -> bool {
+        !self.inner.borrow().receiver_alive
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.inner.borrow_mut().senders += 1;
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut inner = self.inner.borrow_mut();
+        inner.senders -= 1;
+        if inner.senders == 0 {
+            inner.wake_receiver();
+        }
+    }
+}
+
+/// The receiving half of a [`channel`].
+///
+/// Once the receiver is dropped, sending fails.
+#[derive(Debug)]
+pub struct Receiver<T> {
+    inner: Rc<RefCell<Inner<T>>>,
+}
+
+impl<T> Receiver<T> {
+    /// Receive the next value, waiting until one is sent.
+    ///
+    /// Returns `None` once all senders have been dropped and every value they
+    /// sent has been received.
+    pub async fn recv(&mut self) -> Option<T> {
+        future::poll_fn(|cx| {
+            let mut inner = self.inner.borrow_mut();
+            if let Some(value) = inner.queue.pop_front() {
+                // Every waiting sender gets to retry, so a sender which gave up
+                // waiting can't hold up the others.
+                inner.wake_senders();
+                return Poll::Ready(Some(value));
+            }
+            if inner.senders == 0 {
+                return Poll::Ready(None);
+            }
+            inner.recv_waker = Some(cx.waker().clone());
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Receive a value if one is queued, without waiting.
+    pub fn try_recv(&mut self) -> Option<T> {
+        let mut inner = self.inner.borrow_mut();
+        let value = inner.queue.pop_front()?;
+        inner.wake_senders();
+        Some(value)
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut inner = self.inner.borrow_mut();
+        inner.receiver_alive = false;
+        inner.wake_senders();
+    }
+}
+
+/// The error returned by [`Sender::send`] once the receiver has been dropped,
+/// holding the value which couldn't be sent.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SendError").finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the receiving half of the channel was dropped")
+    }
+}
+
+impl<T> core::error::Error for SendError<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exchanges_values_in_order() {
+        let received = crate::block_on(|reactor| async move {
+            let (sender, mut receiver) = channel(4);
+            let producer = reactor.spawn(async move {
+                for i in 0..1000 {
+                    sender.send(i).await.unwrap();
+                }
+            });
+            let consumer = reactor.spawn(async move {
+                let mut received = Vec::new();
+                while let Some(value) = receiver.recv().await {
+                    received.push(value);
+                }
+                received
+            });
+            producer.await;
+            consumer.await
+        });
+        assert_eq!(received, (0..1000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn send_fails_once_receiver_is_dropped() {
+        crate::block_on(|_| async {
+            let (sender, receiver) = channel(1);
+            drop(receiver);
+            assert!(sender.is_closed());
+            let err = sender.send(7).await.unwrap_err();
+            assert_eq!(err.0, 7);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "at least 1")]
+    fn zero_capacity_panics() {
+        let _ = channel::<()>(0);
+    }
+}
@@ -0,0 +1,154 @@
+//! Copying all bytes from a reader into a writer.
+
+use crate::{InputStream, OutputStream};
+
+use futures_lite::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use std::io;
+
+/// The size of the buffer used by [`copy`].
+const DEFAULT_CAPACITY: usize = 8 * 1024;
+
+/// Copy all bytes from `reader` into `writer`, returning the number of bytes
+/// copied.
+///
+/// Bytes are read into a buffer of 8 KiB, which is written out in full before
+/// the next read. The writer is flushed once the reader reaches its end.
+/// Fails with [`io::ErrorKind::WriteZero`] if the writer stops accepting
+/// bytes.
+pub async fn copy<R, W>(reader: &mut R, writer: &mut W) -> io::Result<u64>
+where
+    R: AsyncRead + Unpin + ?Sized,
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    copy_with_capacity(reader, writer, DEFAULT_CAPACITY).await
+}
+
+/// Copy all bytes from `reader` into `writer`, reading at most `capacity`
+/// bytes at a time.
+///
+/// See [`copy`].
+pub async fn copy_with_capacity<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    capacity: usize,
+) -> io::Result<u64>
+where
+    R: AsyncRead + Unpin + ?Sized,
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    let mut buf = vec![0; capacity.max(1)];
+    let mut copied = 0;
+    loop {
+        let len = match reader.read(&mut buf).await {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+
+        // Writes may be partial, so keep going until the chunk is written
+        let mut chunk = &buf[..len];
+        while !chunk.is_empty() {
+            match writer.write(chunk).await {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "the writer stopped accepting bytes",
+                    ))
+                }
+                Ok(written) => chunk = &chunk[written..],
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        copied += len as u64;
+    }
+    writer.flush().await?;
+    Ok(copied)
+}
+
+/// Move all bytes from `src` into `dst`, returning the number of bytes moved.
+///
+/// Bytes are moved with `splice` on the WASI streams, so the host can
+/// transfer them without copying them through the guest. Bytes `src` had
+/// already buffered are written out first, and `dst` is flushed once `src`
+/// reaches its end. If the host can't splice the streams, this falls back to
+/// [`copy`].
+pub async fn splice(src: &mut InputStream, dst: &mut OutputStream) -> io::Result<u64> {
+    src.splice_into(dst).await
+}
+
+#[cfg(test)]
+mod tests {
+    use core::pin::Pin;
+    use core::task::{Context, Poll};
+
+    use super::*;
+
+    /// A writer accepting at most `max` bytes per write.
+    struct Trickle {
+        written: Vec<u8>,
+        max: usize,
+    }
+
+    impl AsyncWrite for Trickle {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            let len = buf.len().min(self.max);
+            self.written.extend_from_slice(&buf[..len]);
+            Poll::Ready(Ok(len))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn copies_everything() {
+        let source: Vec<u8> = (0..20_000).map(|i| i as u8).collect();
+        let (copied, written) = crate::block_on(|_| async {
+            let mut writer = Vec::new();
+            let copied = copy(&mut source.as_slice(), &mut writer).await.unwrap();
+            (copied, writer)
+        });
+        assert_eq!(copied, 20_000);
+        assert_eq!(written, source);
+    }
+
+    #[test]
+    fn finishes_partial_writes() {
+        let source = b"the quick brown fox";
+        let writer = crate::block_on(|_| async {
+            let mut writer = Trickle {
+                written: Vec::new(),
+                max: 3,
+            };
+            let copied = copy_with_capacity(&mut &source[..], &mut writer, 4)
+                .await
+                .unwrap();
+            assert_eq!(copied, source.len() as u64);
+            writer
+        });
+        assert_eq!(writer.written, source);
+    }
+
+    #[test]
+    fn fails_if_writer_stops() {
+        let err = crate::block_on(|_| async {
+            let mut writer = Trickle {
+                written: Vec::new(),
+                max: 0,
+            };
+            copy(&mut &b"bytes"[..], &mut writer).await.unwrap_err()
+        });
+        assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+    }
+}
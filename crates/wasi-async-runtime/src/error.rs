@@ -0,0 +1,93 @@
+//! Errors reported by the WASI I/O streams.
+
+use std::{error, fmt, io};
+use wasi::io::error::Error as WasiError;
+use wasi::io::streams::StreamError as WasiStreamError;
+
+/// The error reported by a WASI stream whose last operation failed.
+///
+/// Stream errors are returned as an [`io::Error`](std::io::Error) wrapping
+/// this type, so the WASI error can be recovered with
+/// [`io::Error::get_ref`](std::io::Error::get_ref) and `downcast_ref`, for
+/// example to inspect it with `wasi:http`'s `http-error-code`.
+pub struct StreamError(WasiError);
+
+impl StreamError {
+    /// Wrap a WASI stream error.
+    pub fn new(err: WasiError) -> Self {
+        Self(err)
+    }
+
+    /// Get the WASI error.
+    pub fn get_ref(&self) -> &WasiError {
+        &self.0
+    }
+
+    /// Unwrap the WASI error.
+    pub fn into_inner(self) -> WasiError {
+        self.0
+    }
+}
+
+impl fmt::Debug for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("StreamError")
+            .field(&self.0.to_debug_string())
+            .finish()
+    }
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the stream operation failed: {}",
+            self.0.to_debug_string()
+        )
+    }
+}
+
+impl error::Error for StreamError {}
+
+/// Convert an error from reading an input stream into an `io::Error`.
+///
+/// Readers usually treat a closed stream as the end of the data, so this
+/// is only reached when the stream closed before an expected read, which
+/// is reported as [`io::ErrorKind::UnexpectedEof`].
+pub(crate) fn read_error(err: WasiStreamError) -> io::Error {
+    match err {
+        WasiStreamError::Closed => io::ErrorKind::UnexpectedEof.into(),
+        WasiStreamError::LastOperationFailed(err) => failed(err),
+    }
+}
+
+/// Convert an error from writing to an output stream into an `io::Error`.
+///
+/// Writing to a closed stream is reported as [`io::ErrorKind::BrokenPipe`].
+pub(crate) fn write_error(err: WasiStreamError) -> io::Error {
+    match err {
+        WasiStreamError::Closed => io::ErrorKind::BrokenPipe.into(),
+        WasiStreamError::LastOperationFailed(err) => failed(err),
+    }
+}
+
+/// Convert the error of a failed stream operation into an `io::Error`,
+/// keeping the WASI error as its source.
+fn failed(err: WasiError) -> io::Error {
+    io::Error::other(StreamError::new(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn closed_streams_map_to_their_own_kinds() {
+        let err = read_error(WasiStreamError::Closed);
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(err.get_ref().is_none());
+        let err = write_error(WasiStreamError::Closed);
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+        assert!(err.get_ref().is_none());
+    }
+}
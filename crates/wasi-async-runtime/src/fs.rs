@@ -0,0 +1,146 @@
+//! Async access to files over `wasi:filesystem`.
+//!
+//! Paths are resolved against the directories preopened by the host.
+//! Relative paths are resolved against the `.` preopen.
+
+use crate::{Reactor, SeekableInputStream};
+
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use futures_lite::{AsyncBufRead, AsyncRead, AsyncSeek};
+use std::io::{self, SeekFrom};
+use std::path::Path;
+use wasi::filesystem::preopens::get_directories;
+use wasi::filesystem::types::{Descriptor, DescriptorFlags, ErrorCode, OpenFlags, PathFlags};
+
+/// Read the entire contents of a file.
+pub async fn read(reactor: &Reactor, path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+    let mut file = File::open(reactor, path)?;
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf).await?;
+    Ok(buf)
+}
+
+/// A file opened for reading.
+///
+/// Reads are driven through the reactor, rather than blocking until data is
+/// available.
+#[derive(Debug)]
+pub struct File {
+    stream: SeekableInputStream,
+}
+
+impl File {
+    /// Open a file for reading.
+    pub fn open(reactor: &Reactor, path: impl AsRef<Path>) -> io::Result<Self> {
+        let (dir, path) = resolve(path.as_ref())?;
+        let descriptor = dir
+            .open_at(
+                PathFlags::SYMLINK_FOLLOW,
+                &path,
+                OpenFlags::empty(),
+                DescriptorFlags::READ,
+            )
+            .map_err(fs_error)?;
+        let stream = SeekableInputStream::new(descriptor, reactor)?;
+        Ok(Self { stream })
+    }
+
+    /// Read all bytes until the end of the file, appending them to `buf`.
+    ///
+    /// See [`InputStream::read_to_end`](crate::InputStream::read_to_end).
+    pub async fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        self.stream.read_to_end(buf).await
+    }
+}
+
+impl AsyncRead for File {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncBufRead for File {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        Pin::new(&mut self.get_mut().stream).poll_fill_buf(cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        Pin::new(&mut self.get_mut().stream).consume(amt)
+    }
+}
+
+impl AsyncSeek for File {
+    fn poll_seek(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        pos: SeekFrom,
+    ) -> Poll<io::Result<u64>> {
+        Pin::new(&mut self.get_mut().stream).poll_seek(cx, pos)
+    }
+}
+
+/// Find the preopened directory `path` lives in, returning it along with the
+/// path relative to it.
+///
+/// If several preopens match, the most specific one wins.
+fn resolve(path: &Path) -> io::Result<(Descriptor, String)> {
+    let mut found: Option<(Descriptor, usize, &Path)> = None;
+    for (dir, name) in get_directories() {
+        let relative = if path.is_relative() && name == "." {
+            Some(path)
+        } else {
+            path.strip_prefix(&name).ok()
+        };
+        let Some(relative) = relative else {
+            continue;
+        };
+        if found.as_ref().is_none_or(|(_, len, _)| name.len() > *len) {
+            found = Some((dir, name.len(), relative));
+        }
+    }
+
+    let Some((dir, _, relative)) = found else {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("`{}` is not in a preopened directory", path.display()),
+        ));
+    };
+    let relative = relative
+        .to_str()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path is not valid UTF-8"))?;
+    let relative = match relative {
+        "" => ".".to_owned(),
+        relative => relative.to_owned(),
+    };
+    Ok((dir, relative))
+}
+
+/// Convert a `wasi:filesystem` error into an `io::Error`, keeping the kind
+/// where one matches.
+pub(crate) fn fs_error(err: ErrorCode) -> io::Error {
+    let kind = match err {
+        ErrorCode::Access | ErrorCode::NotPermitted => io::ErrorKind::PermissionDenied,
+        ErrorCode::WouldBlock => io::ErrorKind::WouldBlock,
+        ErrorCode::Exist => io::ErrorKind::AlreadyExists,
+        ErrorCode::Interrupted => io::ErrorKind::Interrupted,
+        ErrorCode::Invalid => io::ErrorKind::InvalidInput,
+        ErrorCode::IsDirectory => io::ErrorKind::IsADirectory,
+        ErrorCode::NoEntry => io::ErrorKind::NotFound,
+        ErrorCode::InsufficientMemory => io::ErrorKind::OutOfMemory,
+        ErrorCode::InsufficientSpace => io::ErrorKind::StorageFull,
+        ErrorCode::NotDirectory => io::ErrorKind::NotADirectory,
+        ErrorCode::NotEmpty => io::ErrorKind::DirectoryNotEmpty,
+        ErrorCode::Unsupported => io::ErrorKind::Unsupported,
+        ErrorCode::Pipe => io::ErrorKind::BrokenPipe,
+        ErrorCode::ReadOnly => io::ErrorKind::ReadOnlyFilesystem,
+        ErrorCode::FileTooLarge => io::ErrorKind::FileTooLarge,
+        ErrorCode::CrossDevice => io::ErrorKind::CrossesDevices,
+        _ => return io::Error::other(err),
+    };
+    io::Error::new(kind, err)
+}
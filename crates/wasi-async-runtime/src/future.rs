@@ -0,0 +1,280 @@
+//! Combinators for composing futures on the reactor.
+
+use super::{Deadline, Reactor, Timer};
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use alloc::vec::Vec;
+use core::fmt;
+use core::future::{self, Future};
+use core::pin::pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+
+/// The output of [`race`]: the output of whichever future completed first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Either<A, B> {
+    /// The first future completed first.
+    Left(A),
+    /// The second future completed first.
+    Right(B),
+}
+
+/// Wait for the first of two futures to complete.
+///
+/// The futures are polled in order, so if both are ready at the same time
+/// `a` wins. The other future is dropped as soon as a winner is known, which
+/// deregisters any pollables it was waiting on from the reactor.
+pub async fn race<A, B>(a: A, b: B) -> Either<A::Output, B::Output>
+where
+    A: Future,
+    B: Future,
+{
+    let mut a = pin!(a);
+    let mut b = pin!(b);
+    future::poll_fn(|cx| {
+        if let Poll::Ready(output) = a.as_mut().poll(cx) {
+            return Poll::Ready(Either::Left(output));
+        }
+        if let Poll::Ready(output) = b.as_mut().poll(cx) {
+            return Poll::Ready(Either::Right(output));
+        }
+        Poll::Pending
+    })
+    .await
+}
+
+/// Wait for a future to complete, giving up once `dur` has elapsed.
+///
+/// If the timeout is hit first the future is dropped, and
+/// `Err(Elapsed)` is returned. Either way the timer is deregistered from the
+/// reactor as soon as this resolves.
+pub async fn timeout<F: Future>(
+    reactor: &Reactor,
+    dur: Duration,
+    fut: F,
+) -> Result<F::Output, Elapsed> {
+    match race(fut, Timer::after(reactor, dur)).await {
+        Either::Left(output) => Ok(output),
+        Either::Right(()) => Err(Elapsed(())),
+    }
+}
+
+/// Wait for a future to complete, giving up once `deadline` has passed.
+///
+/// Behaves like [`timeout`], except that the time the future gets is what is
+/// left until the deadline, so one deadline can bound several futures.
+pub async fn timeout_at<F: Future>(
+    reactor: &Reactor,
+    deadline: Deadline,
+    fut: F,
+) -> Result<F::Output, Elapsed> {
+    match race(fut, deadline.timer(reactor)).await {
+        Either::Left(output) => Ok(output),
+        Either::Right(()) => Err(Elapsed(())),
+    }
+}
+
+/// The error returned by [`timeout`] and [`timeout_at`] once the timeout has elapsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed(());
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the timeout elapsed before the future completed")
+    }
+}
+
+impl core::error::Error for Elapsed {}
+
+/// Wait for all futures to complete, returning their outputs in the same
+/// order as the futures.
+///
+/// Each future gets its own waker, so when one of them is woken only that
+/// future is polled again, rather than all of them.
+pub async fn join_all<F: Future>(futs: Vec<F>) -> Vec<F::Output> {
+    let mut futs: Vec<_> = futs.into_iter().map(Box::pin).collect();
+    let mut outputs: Vec<Option<F::Output>> = futs.iter().map(|_| None).collect();
+    let mut wakers: Vec<Option<Arc<JoinWaker>>> = futs.iter().map(|_| None).collect();
+    let mut remaining = futs.len();
+
+    future::poll_fn(|cx| {
+        for (index, fut) in futs.iter_mut().enumerate() {
+            if outputs[index].is_some() {
+                continue;
+            }
+
+            // Wakers start out woken, and are replaced whenever we are polled
+            // with a different parent waker.
+            let waker = match &wakers[index] {
+                Some(waker) if waker.parent.will_wake(cx.waker()) => waker.clone(),
+                stale => {
+                    let woken = stale.as_ref().is_none_or(|waker| waker.is_woken());
+                    let waker = JoinWaker::new(cx.waker().clone(), woken);
+                    wakers[index] = Some(waker.clone());
+                    waker
+                }
+            };
+            if !waker.take_woken() {
+                continue;
+            }
+
+            let waker = Waker::from(waker);
+            if let Poll::Ready(output) = fut.as_mut().poll(&mut Context::from_waker(&waker)) {
+                outputs[index] = Some(output);
+                remaining -= 1;
+            }
+        }
+        if remaining == 0 {
+            // NOTE: every future has completed, so every slot holds an output
+            Poll::Ready(outputs.iter_mut().map(|o| o.take().unwrap()).collect())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+/// The waker for a single future in [`join_all`]: marks the future as ready
+/// to be polled, and wakes the task driving `join_all`.
+struct JoinWaker {
+    woken: AtomicBool,
+    parent: Waker,
+}
+
+impl JoinWaker {
+    fn new(parent: Waker, woken: bool) -> Arc<Self> {
+        Arc::new(Self {
+            woken: AtomicBool::new(woken),
+            parent,
+        })
+    }
+
+    fn take_woken(&self) -> bool {
+        self.woken.swap(false, Ordering::Relaxed)
+    }
+
+    fn is_woken(&self) -> bool {
+        self.woken.load(Ordering::Relaxed)
+    }
+}
+
+impl Wake for JoinWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::Relaxed);
+        self.parent.wake_by_ref();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::rc::Rc;
+    use alloc::vec;
+    use core::cell::{Cell, RefCell};
+    use core::pin::Pin;
+
+    use super::*;
+
+    #[test]
+    fn race_returns_first_ready() {
+        crate::block_on(|_| async {
+            let left = race(async { 1 }, future::pending::<()>()).await;
+            assert_eq!(left, Either::Left(1));
+            let right = race(future::pending::<()>(), async { 2 }).await;
+            assert_eq!(right, Either::Right(2));
+        });
+    }
+
+    #[test]
+    fn race_drops_loser() {
+        struct SetOnDrop(Rc<Cell<bool>>);
+        impl Drop for SetOnDrop {
+            fn drop(&mut self) {
+                self.0.set(true);
+            }
+        }
+
+        let dropped = Rc::new(Cell::new(false));
+        let guard = SetOnDrop(dropped.clone());
+        crate::block_on(|_| async move {
+            let loser = async move {
+                let _guard = guard;
+                future::pending::<()>().await
+            };
+            race(async { 1 }, loser).await;
+            assert!(dropped.get());
+        });
+    }
+
+    #[test]
+    fn race_prefers_first_when_both_ready() {
+        crate::block_on(|_| async {
+            assert_eq!(race(async { 1 }, async { 2 }).await, Either::Left(1));
+        });
+    }
+
+    #[test]
+    fn join_all_keeps_order() {
+        let futs: Vec<_> = (0..5)
+            .map(|i| {
+                let mut left = 4 - i;
+                future::poll_fn(move |cx| {
+                    if left == 0 {
+                        return Poll::Ready(i);
+                    }
+                    left -= 1;
+                    cx.waker().wake_by_ref();
+                    Poll::Pending
+                })
+            })
+            .collect();
+        let outputs = crate::block_on(|_| join_all(futs));
+        assert_eq!(outputs, [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn join_all_polls_only_woken_futures() {
+        // The waiter is only woken once the other future is done, while the
+        // other future wakes itself a few times before that
+        let waiter_polls = Rc::new(Cell::new(0));
+        let waker = Rc::new(RefCell::new(None::<Waker>));
+        let done = Rc::new(Cell::new(false));
+        let waiter = {
+            let (polls, waker, done) = (waiter_polls.clone(), waker.clone(), done.clone());
+            Box::pin(future::poll_fn(move |cx| {
+                polls.set(polls.get() + 1);
+                if done.get() {
+                    return Poll::Ready(());
+                }
+                *waker.borrow_mut() = Some(cx.waker().clone());
+                Poll::Pending
+            })) as Pin<Box<dyn Future<Output = ()>>>
+        };
+        let mut left = 3;
+        let other = Box::pin(future::poll_fn(move |cx| {
+            if left == 0 {
+                done.set(true);
+                waker.borrow_mut().take().unwrap().wake();
+                return Poll::Ready(());
+            }
+            left -= 1;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }));
+
+        crate::block_on(|_| join_all(vec![waiter, other]));
+        assert_eq!(waiter_polls.get(), 2);
+    }
+
+    #[test]
+    fn join_all_of_nothing_is_empty() {
+        let outputs = crate::block_on(|_| join_all(Vec::<future::Ready<()>>::new()));
+        assert!(outputs.is_empty());
+    }
+}
@@ -7,16 +7,67 @@
 //! This will automatically wait for the futures to resolve, and call the
 //! necessary wakers to work.
 
-#![forbid(rust_2018_idioms)]
+#![forbid(unsafe_code, rust_2018_idioms)]
 #![deny(missing_debug_implementations, nonstandard_style)]
 #![warn(missing_docs, future_incompatible, unreachable_pub)]
 #![cfg_attr(not(feature = "std"), no_std)]
 
 extern crate alloc;
 
+mod abort;
 mod block_on;
+mod cancel;
+mod channel;
+#[cfg(feature = "std")]
+mod copy;
+#[cfg(feature = "std")]
+mod error;
+#[cfg(feature = "std")]
+pub mod fs;
+mod future;
+#[cfg(feature = "std")]
+mod line_writer;
+#[cfg(feature = "std")]
+mod metered;
+pub mod monotonic;
+mod mutex;
+#[cfg(feature = "std")]
+mod net;
+mod once_cell;
 mod polling;
 mod reactor;
+#[cfg(feature = "std")]
+pub mod stdio;
+#[cfg(feature = "std")]
+mod streams;
+mod task;
+#[cfg(feature = "std")]
+mod throttle;
+mod time;
+mod wait_group;
 
-pub use block_on::block_on;
-pub use reactor::Reactor;
+pub use abort::{abortable, AbortHandle, Abortable, Aborted};
+pub use block_on::{block_on, block_on_abort};
+pub use cancel::CancellationToken;
+pub use channel::{channel, Receiver, SendError, Sender};
+#[cfg(feature = "std")]
+pub use copy::{copy, copy_with_capacity, splice};
+#[cfg(feature = "std")]
+pub use error::StreamError;
+pub use future::{join_all, race, timeout, timeout_at, Either, Elapsed};
+#[cfg(feature = "std")]
+pub use line_writer::LineWriter;
+#[cfg(feature = "std")]
+pub use metered::Metered;
+pub use mutex::{Mutex, MutexGuard};
+#[cfg(feature = "std")]
+pub use net::{resolve, TcpListener, TcpStream, UdpSocket};
+pub use once_cell::OnceCell;
+pub use reactor::{PollHandle, Reactor, ReactorStats};
+#[cfg(feature = "std")]
+pub use streams::{Chunks, InputStream, OutputStream, SeekableInputStream};
+pub use task::JoinHandle;
+#[cfg(feature = "std")]
+pub use throttle::Throttle;
+pub use time::{Deadline, Interval, MissedTickBehavior, Timer};
+pub use wait_group::{WaitGroup, Worker};
@@ -0,0 +1,232 @@
+//! A writer which flushes on every completed line.
+
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use futures_lite::{ready, AsyncWrite};
+use std::io;
+
+/// The number of bytes buffered before they are written out, even if no line
+/// has been completed.
+const DEFAULT_CAPACITY: usize = 1024;
+
+/// Wraps a writer such as an [`OutputStream`](crate::OutputStream), and
+/// flushes it whenever a line has been completed.
+///
+/// Writes are buffered until they contain a `\n`, or exceed the capacity of
+/// the buffer. If a single write contains several lines, all completed lines
+/// are flushed together, while the partial line after the last `\n` stays
+/// buffered. Closing the writer flushes any partial line.
+#[derive(Debug)]
+pub struct LineWriter<W> {
+    inner: W,
+    buf: Vec<u8>,
+    /// How much of `buf` has already been written to `inner`.
+    written: usize,
+    /// Whether `buf` holds a completed line which has yet to be flushed.
+    line: bool,
+    capacity: usize,
+    /// An error from flushing a line early, to be returned by the next call.
+    error: Option<io::Error>,
+}
+
+impl<W: AsyncWrite + Unpin> LineWriter<W> {
+    /// Wrap a writer.
+    pub fn new(inner: W) -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY, inner)
+    }
+
+    /// Wrap a writer, buffering up to `capacity` bytes of a partial line.
+    pub fn with_capacity(capacity: usize, inner: W) -> Self {
+        Self {
+            inner,
+            buf: Vec::with_capacity(capacity),
+            written: 0,
+            line: false,
+            capacity,
+            error: None,
+        }
+    }
+
+    /// Get a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying writer.
+    ///
+    /// Writing to it directly bypasses anything still buffered.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Unwrap the underlying writer, discarding anything still buffered.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Write everything buffered out to the inner writer.
+    fn poll_write_buf(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.written < self.buf.len() {
+            let len = ready!(Pin::new(&mut self.inner).poll_write(cx, &self.buf[self.written..]))?;
+            if len == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.written += len;
+        }
+        self.buf.clear();
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+
+    /// Write everything buffered out, and flush the inner writer if that
+    /// included a completed line.
+    fn poll_write_lines(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_write_buf(cx))?;
+        if self.line {
+            ready!(Pin::new(&mut self.inner).poll_flush(cx))?;
+            self.line = false;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for LineWriter<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        data: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if let Some(err) = this.error.take() {
+            return Poll::Ready(Err(err));
+        }
+
+        // Completed lines which couldn't be flushed right away, or a buffer
+        // which would overflow, need to go out before we take on more data.
+        if this.line || this.buf.len() + data.len() > this.capacity {
+            ready!(this.poll_write_lines(cx))?;
+        }
+
+        match data.iter().rposition(|&byte| byte == b'\n') {
+            Some(index) => {
+                // Take everything up to and including the last newline, and
+                // try to flush it right away. If the inner writer isn't ready
+                // it will be flushed on the next write instead; either way
+                // the data has been accepted. A failure is reported by the
+                // next call.
+                this.buf.extend_from_slice(&data[..=index]);
+                this.line = true;
+                if let Poll::Ready(Err(err)) = this.poll_write_lines(cx) {
+                    this.error = Some(err);
+                }
+                Poll::Ready(Ok(index + 1))
+            }
+            // Partial lines too big to buffer are written out directly
+            None if data.len() >= this.capacity => Pin::new(&mut this.inner).poll_write(cx, data),
+            None => {
+                this.buf.extend_from_slice(data);
+                Poll::Ready(Ok(data.len()))
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if let Some(err) = this.error.take() {
+            return Poll::Ready(Err(err));
+        }
+        ready!(this.poll_write_buf(cx))?;
+        this.line = false;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if let Some(err) = this.error.take() {
+            return Poll::Ready(Err(err));
+        }
+        ready!(this.poll_write_buf(cx))?;
+        this.line = false;
+        Pin::new(&mut this.inner).poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_lite::AsyncWriteExt;
+
+    /// A writer recording what was written, and what had been flushed. If
+    /// `fail` is set, the next write fails.
+    #[derive(Default)]
+    struct Recorder {
+        written: Vec<u8>,
+        flushed: usize,
+        fail: bool,
+    }
+
+    impl AsyncWrite for Recorder {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            data: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            if std::mem::take(&mut self.fail) {
+                return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+            }
+            self.written.extend_from_slice(data);
+            Poll::Ready(Ok(data.len()))
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.flushed = self.written.len();
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.poll_flush(cx)
+        }
+    }
+
+    #[test]
+    fn flushes_completed_lines() {
+        crate::block_on(|_| async {
+            let mut writer = LineWriter::new(Recorder::default());
+            writer.write_all(b"one\ntw").await.unwrap();
+            assert_eq!(writer.get_ref().written, b"one\n");
+            assert_eq!(writer.get_ref().flushed, 4);
+
+            writer.write_all(b"o\n").await.unwrap();
+            assert_eq!(writer.get_ref().written, b"one\ntwo\n");
+            assert_eq!(writer.get_ref().flushed, 8);
+        });
+    }
+
+    #[test]
+    fn close_flushes_partial_line() {
+        crate::block_on(|_| async {
+            let mut writer = LineWriter::new(Recorder::default());
+            writer.write_all(b"partial").await.unwrap();
+            assert!(writer.get_ref().written.is_empty());
+            writer.close().await.unwrap();
+            assert_eq!(writer.get_ref().written, b"partial");
+        });
+    }
+
+    #[test]
+    fn reports_failed_line_flush() {
+        crate::block_on(|_| async {
+            let mut writer = LineWriter::new(Recorder {
+                fail: true,
+                ..Recorder::default()
+            });
+            // The line is accepted, and the failure to write it out is
+            // returned by the next call, even though a retry would succeed
+            assert_eq!(writer.write(b"line\n").await.unwrap(), 5);
+            let err = writer.flush().await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+            writer.flush().await.unwrap();
+            assert_eq!(writer.get_ref().written, b"line\n");
+        });
+    }
+}
@@ -0,0 +1,194 @@
+//! Counting the bytes moved through readers and writers.
+
+use alloc::boxed::Box;
+use core::fmt;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use futures_lite::{AsyncBufRead, AsyncRead, AsyncWrite};
+use std::io;
+
+/// Wraps a reader or writer, such as an [`InputStream`](crate::InputStream)
+/// or [`OutputStream`](crate::OutputStream), counting the bytes moved through
+/// it.
+///
+/// Every read or write which completes successfully counts as one operation,
+/// including reads which report the end of the stream. Flushes and closes
+/// aren't counted. When wrapping a type which is both a reader and a writer,
+/// the counts cover both directions.
+pub struct Metered<T> {
+    inner: T,
+    bytes: u64,
+    ops: u64,
+    callback: Option<Box<dyn FnMut(usize)>>,
+}
+
+impl<T> Metered<T> {
+    /// Wrap a reader or writer.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            bytes: 0,
+            ops: 0,
+            callback: None,
+        }
+    }
+
+    /// Wrap a reader or writer, calling `callback` with the number of bytes
+    /// moved after every operation.
+    pub fn with_callback(inner: T, callback: impl FnMut(usize) + 'static) -> Self {
+        Self {
+            callback: Some(Box::new(callback)),
+            ..Self::new(inner)
+        }
+    }
+
+    /// The total number of bytes read or written so far.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// The number of reads and writes which completed so far.
+    pub fn ops(&self) -> u64 {
+        self.ops
+    }
+
+    /// Get a reference to the wrapped reader or writer.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the wrapped reader or writer.
+    ///
+    /// Reading or writing through it directly isn't counted.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Unwrap the reader or writer.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Count an operation which moved `len` bytes.
+    fn record(&mut self, len: usize) {
+        self.bytes += len as u64;
+        self.ops += 1;
+        if let Some(callback) = &mut self.callback {
+            callback(len);
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Metered<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Metered")
+            .field("inner", &self.inner)
+            .field("bytes", &self.bytes)
+            .field("ops", &self.ops)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Metered<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let res = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(len)) = res {
+            this.record(len);
+        }
+        res
+    }
+}
+
+impl<T: AsyncBufRead + Unpin> AsyncBufRead for Metered<T> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        Pin::new(&mut self.get_mut().inner).poll_fill_buf(cx)
+    }
+
+    /// Bytes read through the buffer are counted once they're consumed.
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        Pin::new(&mut this.inner).consume(amt);
+        this.record(amt);
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Metered<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let res = Pin::new(&mut this.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(len)) = res {
+            this.record(len);
+        }
+        res
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::rc::Rc;
+    use core::cell::Cell;
+    use futures_lite::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    #[test]
+    fn counts_reads() {
+        crate::block_on(|_| async {
+            let mut reader = Metered::new(&b"hello world"[..]);
+            let mut buf = [0; 4];
+            reader.read_exact(&mut buf).await.unwrap();
+            let mut rest = Vec::new();
+            reader.read_to_end(&mut rest).await.unwrap();
+            assert_eq!(rest, b"o world");
+            assert_eq!(Metered::bytes(&reader), 11);
+            // The read reporting the end of the stream is counted as well
+            assert_eq!(reader.ops(), 3);
+        });
+    }
+
+    #[test]
+    fn counts_consumed_bytes() {
+        crate::block_on(|_| async {
+            let mut reader = Metered::new(&b"hello"[..]);
+            let len = reader.fill_buf().await.unwrap().len();
+            assert_eq!(Metered::bytes(&reader), 0);
+            reader.consume(len);
+            assert_eq!(Metered::bytes(&reader), 5);
+        });
+    }
+
+    #[test]
+    fn counts_writes_and_calls_back() {
+        let seen = Rc::new(Cell::new(0));
+        let total = seen.clone();
+        crate::block_on(|_| async move {
+            let mut writer = Metered::with_callback(Vec::new(), move |len| {
+                total.set(total.get() + len);
+            });
+            writer.write_all(b"one").await.unwrap();
+            writer.write_all(b"two").await.unwrap();
+            writer.flush().await.unwrap();
+            assert_eq!(writer.bytes(), 6);
+            assert_eq!(writer.ops(), 2);
+            assert_eq!(writer.into_inner(), b"onetwo");
+        });
+        assert_eq!(seen.get(), 6);
+    }
+}
@@ -0,0 +1,86 @@
+//! Points in time measured by the WASI monotonic clock.
+//!
+//! This doesn't depend on `std::time`, so it can be used in `no_std` guests.
+
+use core::ops::{Add, AddAssign, Sub};
+use core::time::Duration;
+use wasi::clocks::monotonic_clock;
+
+/// A point in time measured by the WASI monotonic clock.
+///
+/// The clock is not related to wall-clock time, and can only be used to
+/// measure time elapsed between two instants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant {
+    nanos: u64,
+}
+
+/// Get the current time on the monotonic clock.
+pub fn now() -> Instant {
+    Instant::now()
+}
+
+impl Instant {
+    /// Get the current time on the monotonic clock.
+    pub fn now() -> Self {
+        Self {
+            nanos: monotonic_clock::now(),
+        }
+    }
+
+    /// Get the time elapsed since this instant.
+    pub fn elapsed(&self) -> Duration {
+        now().duration_since(*self)
+    }
+
+    /// Get the time elapsed from `earlier` to this instant, or zero if
+    /// `earlier` is later than this instant.
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        Duration::from_nanos(self.nanos.saturating_sub(earlier.nanos))
+    }
+
+    /// Add a duration to this instant, returning `None` on overflow.
+    pub fn checked_add(&self, dur: Duration) -> Option<Instant> {
+        let nanos = u64::try_from(dur.as_nanos()).ok()?;
+        Some(Self {
+            nanos: self.nanos.checked_add(nanos)?,
+        })
+    }
+
+    /// Get the raw nanosecond value the WASI clock works in.
+    pub(crate) fn as_nanos(&self) -> u64 {
+        self.nanos
+    }
+
+    /// Create an instant from a raw nanosecond value, without reading the
+    /// clock.
+    #[cfg(test)]
+    pub(crate) fn from_nanos(nanos: u64) -> Self {
+        Self { nanos }
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    /// Panics on overflow; see [`Instant::checked_add`] for a fallible
+    /// version.
+    fn add(self, dur: Duration) -> Instant {
+        self.checked_add(dur)
+            .expect("overflow when adding duration to instant")
+    }
+}
+
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, dur: Duration) {
+        *self = *self + dur;
+    }
+}
+
+impl Sub<Instant> for Instant {
+    type Output = Duration;
+
+    fn sub(self, earlier: Instant) -> Duration {
+        self.duration_since(earlier)
+    }
+}
@@ -0,0 +1,248 @@
+//! An async lock for sharing state between tasks.
+
+use alloc::collections::VecDeque;
+use core::cell::{RefCell, RefMut};
+use core::future;
+use core::ops::{Deref, DerefMut};
+use core::task::{Poll, Waker};
+
+/// An async mutual exclusion lock.
+///
+/// Tasks waiting for the lock are parked until it is released, and are handed
+/// the lock in the order they started waiting in. Since the runtime is
+/// single-threaded, the mutex is meant to be shared through an
+/// [`Rc`](alloc::rc::Rc) rather than an `Arc`.
+#[derive(Debug, Default)]
+pub struct Mutex<T> {
+    state: RefCell<State>,
+    value: RefCell<T>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    locked: bool,
+    waiters: VecDeque<Waiter>,
+    /// The waiter the lock was handed to, which hasn't picked it up yet.
+    granted: Option<u64>,
+    next_id: u64,
+}
+
+#[derive(Debug)]
+struct Waiter {
+    id: u64,
+    waker: Waker,
+}
+
+impl<T> Mutex<T> {
+    /// Create a new, unlocked mutex holding `value`.
+    pub fn new(value: T) -> Self {
+        Self {
+            state: RefCell::new(State::default()),
+            value: RefCell::new(value),
+        }
+    }
+
+    /// Wait until the lock is acquired.
+    ///
+    /// The lock is released once the returned guard is dropped. Dropping the
+    /// future while it waits gives up its place in the queue.
+    pub async fn lock(&self) -> MutexGuard<'_, T> {
+        let mut waiting = Waiting {
+            mutex: self,
+            id: None,
+        };
+        future::poll_fn(|cx| {
+            let mut state = self.state.borrow_mut();
+            match waiting.id {
+                None if !state.locked => {
+                    state.locked = true;
+                    Poll::Ready(())
+                }
+                None => {
+                    let id = state.next_id;
+                    state.next_id += 1;
+                    let waker = cx.waker().clone();
+                    state.waiters.push_back(Waiter { id, waker });
+                    waiting.id = Some(id);
+                    Poll::Pending
+                }
+                Some(id) if state.granted == Some(id) => {
+                    state.granted = None;
+                    waiting.id = None;
+                    Poll::Ready(())
+                }
+                Some(id) => {
+                    if let Some(waiter) = state.waiters.iter_mut().find(|w| w.id == id) {
+                        if !waiter.waker.will_wake(cx.waker()) {
+                            waiter.waker = cx.waker().clone();
+                        }
+                    }
+                    Poll::Pending
+                }
+            }
+        })
+        .await;
+        self.guard()
+    }
+
+    /// Acquire the lock if it is free, without waiting.
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        let mut state = self.state.borrow_mut();
+        if state.locked {
+            return None;
+        }
+        state.locked = true;
+        drop(state);
+        Some(self.guard())
+    }
+
+    /// Get a mutable reference to the value, which needs no locking since the
+    /// mutex is borrowed mutably.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    /// Unwrap the value held by the mutex.
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+
+    fn guard(&self) -> MutexGuard<'_, T> {
+        MutexGuard {
+            mutex: self,
+            // NOTE: only one guard exists at a time, so this never fails
+            value: self.value.borrow_mut(),
+        }
+    }
+
+    /// Hand the lock to the next waiter, or unlock it if there is none.
+    fn release(&self) {
+        let mut state = self.state.borrow_mut();
+        match state.waiters.pop_front() {
+            Some(waiter) => {
+                state.granted = Some(waiter.id);
+                drop(state);
+                waiter.waker.wake();
+            }
+            None => state.locked = false,
+        }
+    }
+}
+
+/// Removes a waiter from the queue if its `lock` future is dropped early.
+struct Waiting<'a, T> {
+    mutex: &'a Mutex<T>,
+    id: Option<u64>,
+}
+
+impl<T> Drop for Waiting<'_, T> {
+    fn drop(&mut self) {
+        let Some(id) = self.id else {
+            return;
+        };
+        let mut state = self.mutex.state.borrow_mut();
+        if state.granted == Some(id) {
+            // We were handed the lock but never picked it up, so pass it on
+            state.granted = None;
+            drop(state);
+            self.mutex.release();
+        } else {
+            state.waiters.retain(|waiter| waiter.id != id);
+        }
+    }
+}
+
+/// A guard holding the lock of a [`Mutex`], which releases it once dropped.
+#[derive(Debug)]
+pub struct MutexGuard<'a, T> {
+    mutex: &'a Mutex<T>,
+    value: RefMut<'a, T>,
+}
+
+impl<T> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        // The borrow of the value is only released after this returns, but
+        // the next holder can't pick up the lock until its task is polled
+        // again, so it never observes the value as borrowed.
+        self.mutex.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::rc::Rc;
+    use alloc::vec::Vec;
+    use core::future::Future;
+    use futures_lite::future::yield_now;
+
+    use super::*;
+
+    #[test]
+    fn no_lost_updates() {
+        let count = crate::block_on(|reactor| async move {
+            let counter = Rc::new(Mutex::new(0));
+            let handles: Vec<_> = (0..2)
+                .map(|_| {
+                    let counter = counter.clone();
+                    reactor.spawn(async move {
+                        for _ in 0..100 {
+                            let mut guard = counter.lock().await;
+                            // Yield while holding the lock, so the other task
+                            // tries to take it in between
+                            let value = *guard;
+                            yield_now().await;
+                            *guard = value + 1;
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                handle.await;
+            }
+            let count = *counter.lock().await;
+            count
+        });
+        assert_eq!(count, 200);
+    }
+
+    #[test]
+    fn try_lock_fails_while_locked() {
+        crate::block_on(|_| async {
+            let mutex = Mutex::new(());
+            let guard = mutex.lock().await;
+            assert!(mutex.try_lock().is_none());
+            drop(guard);
+            assert!(mutex.try_lock().is_some());
+        });
+    }
+
+    #[test]
+    fn dropped_waiter_gives_up_its_place() {
+        crate::block_on(|_| async {
+            let mutex = Mutex::new(());
+            let guard = mutex.lock().await;
+            {
+                let mut waiting = core::pin::pin!(mutex.lock());
+                let pending = future::poll_fn(|cx| Poll::Ready(waiting.as_mut().poll(cx))).await;
+                assert!(pending.is_pending());
+            }
+            drop(guard);
+            // The lock isn't handed to the waiter which went away
+            assert!(mutex.try_lock().is_some());
+        });
+    }
+}
@@ -0,0 +1,108 @@
+//! Networking over `wasi:sockets`.
+
+use crate::Reactor;
+
+use core::future;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use wasi::io::poll::Pollable;
+use wasi::sockets::network::{
+    ErrorCode, IpAddressFamily, IpSocketAddress, Ipv4SocketAddress, Ipv6SocketAddress,
+};
+
+pub use resolve::resolve;
+pub use tcp_listener::TcpListener;
+pub use tcp_stream::TcpStream;
+pub use udp_socket::UdpSocket;
+
+mod resolve;
+mod tcp_listener;
+mod tcp_stream;
+mod udp_socket;
+
+/// Drive a `wasi:sockets` operation which reports `WouldBlock` until it can
+/// complete, waiting on `pollable` in between attempts.
+async fn finish<T>(
+    reactor: &Reactor,
+    pollable: Pollable,
+    mut op: impl FnMut() -> Result<T, ErrorCode>,
+) -> io::Result<T> {
+    let handle = reactor.register(pollable);
+    loop {
+        match op() {
+            Ok(value) => return Ok(value),
+            Err(ErrorCode::WouldBlock) => future::poll_fn(|cx| handle.poll(cx)).await,
+            Err(err) => return Err(socket_error(err)),
+        }
+    }
+}
+
+/// Get the address family of a socket address.
+fn family(addr: &SocketAddr) -> IpAddressFamily {
+    match addr {
+        SocketAddr::V4(_) => IpAddressFamily::Ipv4,
+        SocketAddr::V6(_) => IpAddressFamily::Ipv6,
+    }
+}
+
+/// Convert a socket address into its WASI representation.
+fn to_wasi_addr(addr: SocketAddr) -> IpSocketAddress {
+    match addr {
+        SocketAddr::V4(addr) => {
+            let [a, b, c, d] = addr.ip().octets();
+            IpSocketAddress::Ipv4(Ipv4SocketAddress {
+                port: addr.port(),
+                address: (a, b, c, d),
+            })
+        }
+        SocketAddr::V6(addr) => {
+            let [a, b, c, d, e, f, g, h] = addr.ip().segments();
+            IpSocketAddress::Ipv6(Ipv6SocketAddress {
+                port: addr.port(),
+                flow_info: addr.flowinfo(),
+                address: (a, b, c, d, e, f, g, h),
+                scope_id: addr.scope_id(),
+            })
+        }
+    }
+}
+
+/// Convert a WASI socket address into a `SocketAddr`.
+fn from_wasi_addr(addr: IpSocketAddress) -> SocketAddr {
+    match addr {
+        IpSocketAddress::Ipv4(addr) => {
+            let (a, b, c, d) = addr.address;
+            SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(a, b, c, d), addr.port))
+        }
+        IpSocketAddress::Ipv6(addr) => {
+            let (a, b, c, d, e, f, g, h) = addr.address;
+            SocketAddr::V6(SocketAddrV6::new(
+                Ipv6Addr::new(a, b, c, d, e, f, g, h),
+                addr.port,
+                addr.flow_info,
+                addr.scope_id,
+            ))
+        }
+    }
+}
+
+/// Convert a `wasi:sockets` error into an `io::Error`, keeping the kind where
+/// one matches.
+fn socket_error(err: ErrorCode) -> io::Error {
+    let kind = match err {
+        ErrorCode::AccessDenied => io::ErrorKind::PermissionDenied,
+        ErrorCode::NotSupported => io::ErrorKind::Unsupported,
+        ErrorCode::InvalidArgument => io::ErrorKind::InvalidInput,
+        ErrorCode::OutOfMemory => io::ErrorKind::OutOfMemory,
+        ErrorCode::Timeout => io::ErrorKind::TimedOut,
+        ErrorCode::WouldBlock => io::ErrorKind::WouldBlock,
+        ErrorCode::AddressNotBindable => io::ErrorKind::AddrNotAvailable,
+        ErrorCode::AddressInUse => io::ErrorKind::AddrInUse,
+        ErrorCode::ConnectionRefused => io::ErrorKind::ConnectionRefused,
+        ErrorCode::ConnectionReset => io::ErrorKind::ConnectionReset,
+        ErrorCode::ConnectionAborted => io::ErrorKind::ConnectionAborted,
+        ErrorCode::NameUnresolvable => io::ErrorKind::NotFound,
+        _ => return io::Error::other(err),
+    };
+    io::Error::new(kind, err)
+}
@@ -0,0 +1,55 @@
+use super::socket_error;
+use crate::Reactor;
+
+use core::future;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use wasi::sockets::instance_network::instance_network;
+use wasi::sockets::ip_name_lookup::resolve_addresses;
+use wasi::sockets::network::{ErrorCode, IpAddress};
+
+/// Resolve a host name to its IP addresses.
+///
+/// A name which is already an IP address is returned as-is, without a
+/// lookup. Fails with [`io::ErrorKind::NotFound`] if the name doesn't resolve
+/// to any addresses.
+pub async fn resolve(reactor: &Reactor, name: &str) -> io::Result<Vec<IpAddr>> {
+    if let Ok(addr) = name.parse::<IpAddr>() {
+        return Ok(vec![addr]);
+    }
+
+    let network = instance_network();
+    let stream = resolve_addresses(&network, name).map_err(socket_error)?;
+
+    // The handle is a child of the stream, so it is dropped before it
+    let mut addrs = Vec::new();
+    {
+        let handle = reactor.register(stream.subscribe());
+        loop {
+            match stream.resolve_next_address() {
+                Ok(Some(addr)) => addrs.push(from_wasi_ip(addr)),
+                Ok(None) => break,
+                Err(ErrorCode::WouldBlock) => future::poll_fn(|cx| handle.poll(cx)).await,
+                Err(err) => return Err(socket_error(err)),
+            }
+        }
+    }
+
+    if addrs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no addresses found for `{name}`"),
+        ));
+    }
+    Ok(addrs)
+}
+
+/// Convert a WASI IP address into an `IpAddr`.
+fn from_wasi_ip(addr: IpAddress) -> IpAddr {
+    match addr {
+        IpAddress::Ipv4((a, b, c, d)) => IpAddr::V4(Ipv4Addr::new(a, b, c, d)),
+        IpAddress::Ipv6((a, b, c, d, e, f, g, h)) => {
+            IpAddr::V6(Ipv6Addr::new(a, b, c, d, e, f, g, h))
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use super::{family, finish, from_wasi_addr, socket_error, to_wasi_addr, TcpStream};
+use crate::Reactor;
+
+use std::io;
+use std::net::SocketAddr;
+use wasi::sockets::instance_network::instance_network;
+use wasi::sockets::tcp::TcpSocket;
+use wasi::sockets::tcp_create_socket::create_tcp_socket;
+
+/// A TCP socket listening for incoming connections.
+///
+/// WASI sets the equivalent of `SO_REUSEADDR` on all TCP sockets, so a
+/// listener can be bound again right after a previous one on the same address
+/// has been closed.
+#[derive(Debug)]
+pub struct TcpListener {
+    socket: TcpSocket,
+    reactor: Reactor,
+}
+
+impl TcpListener {
+    /// Bind a listener to `addr`.
+    ///
+    /// Binding to port `0` picks an ephemeral port, which can be obtained
+    /// through [`TcpListener::local_addr`].
+    pub async fn bind(reactor: &Reactor, addr: SocketAddr) -> io::Result<Self> {
+        let network = instance_network();
+        let socket = create_tcp_socket(family(&addr)).map_err(socket_error)?;
+
+        socket
+            .start_bind(&network, to_wasi_addr(addr))
+            .map_err(socket_error)?;
+        finish(reactor, socket.subscribe(), || socket.finish_bind()).await?;
+
+        socket.start_listen().map_err(socket_error)?;
+        finish(reactor, socket.subscribe(), || socket.finish_listen()).await?;
+
+        Ok(Self {
+            socket,
+            reactor: reactor.clone(),
+        })
+    }
+
+    /// Set the maximum number of connections waiting to be accepted.
+    ///
+    /// Hosts may round this, or ignore it altogether.
+    pub fn set_backlog_size(&self, size: u64) -> io::Result<()> {
+        self.socket
+            .set_listen_backlog_size(size)
+            .map_err(socket_error)
+    }
+
+    /// Get the local address the listener is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        let addr = self.socket.local_address().map_err(socket_error)?;
+        Ok(from_wasi_addr(addr))
+    }
+
+    /// Wait for an incoming connection and accept it.
+    pub async fn accept(&self) -> io::Result<TcpStream> {
+        let (socket, input, output) = finish(&self.reactor, self.socket.subscribe(), || {
+            self.socket.accept()
+        })
+        .await?;
+        Ok(TcpStream::from_parts(socket, input, output, &self.reactor))
+    }
+}
@@ -0,0 +1,324 @@
+use super::{family, finish, from_wasi_addr, resolve, socket_error, to_wasi_addr};
+use crate::{InputStream, OutputStream, Reactor, Timer};
+
+use core::future::{self, Future};
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use core::time::Duration;
+use futures_lite::{AsyncBufRead, AsyncRead, AsyncWrite};
+use std::io::{self, IoSlice};
+use std::net::SocketAddr;
+use wasi::io::streams::{InputStream as WasiInputStream, OutputStream as WasiOutputStream};
+use wasi::sockets::instance_network::instance_network;
+use wasi::sockets::tcp::{ShutdownType, TcpSocket};
+use wasi::sockets::tcp_create_socket::create_tcp_socket;
+
+/// How long a connection attempt gets before the next address is tried
+/// alongside it, as recommended by RFC 8305.
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// A TCP connection.
+///
+/// Reading and writing go through the socket's [`InputStream`] and
+/// [`OutputStream`], which can also be accessed directly.
+#[derive(Debug)]
+pub struct TcpStream {
+    // IMPORTANT: the order of these fields here matters. The streams are
+    // children of `socket`, so they must be dropped first.
+    input: InputStream,
+    output: OutputStream,
+    socket: TcpSocket,
+}
+
+impl TcpStream {
+    /// Open a TCP connection to `addr`.
+    ///
+    /// Resolves once the connection has been established, or fails if it was
+    /// refused or couldn't be made.
+    pub async fn connect(reactor: &Reactor, addr: SocketAddr) -> io::Result<Self> {
+        let network = instance_network();
+        let socket = create_tcp_socket(family(&addr)).map_err(socket_error)?;
+        socket
+            .start_connect(&network, to_wasi_addr(addr))
+            .map_err(socket_error)?;
+
+        let (input, output) =
+            finish(reactor, socket.subscribe(), || socket.finish_connect()).await?;
+        Ok(Self::from_parts(socket, input, output, reactor))
+    }
+
+    /// Open a TCP connection to `port` on `host`.
+    ///
+    /// The host is resolved with [`resolve`], after which its addresses are
+    /// connected to as described in [`TcpStream::connect_addrs`].
+    pub async fn connect_host(reactor: &Reactor, host: &str, port: u16) -> io::Result<Self> {
+        let addrs: Vec<_> = resolve(reactor, host)
+            .await?
+            .into_iter()
+            .map(|ip| SocketAddr::new(ip, port))
+            .collect();
+        Self::connect_addrs(reactor, &addrs).await
+    }
+
+    /// Open a TCP connection to the first of `addrs` which accepts it.
+    ///
+    /// Connections are raced as described in
+    /// [RFC 8305](https://www.rfc-editor.org/rfc/rfc8305) ("happy
+    /// eyeballs"): the addresses are interleaved by family, starting with the
+    /// family of the first address, and a new attempt is started whenever
+    /// the previous one fails, or hasn't connected within 250ms. The first
+    /// connection to be established is used, and the other attempts are
+    /// cancelled. Fails with the error of the last attempt if none of the
+    /// addresses could be connected to, or with
+    /// [`io::ErrorKind::InvalidInput`] if `addrs` is empty.
+    pub async fn connect_addrs(reactor: &Reactor, addrs: &[SocketAddr]) -> io::Result<Self> {
+        let mut pending = interleave(addrs).into_iter();
+        let mut attempts: Vec<Pin<Box<dyn Future<Output = io::Result<Self>> + '_>>> = Vec::new();
+        let mut delay: Option<Timer> = None;
+        let mut last_err = None;
+        future::poll_fn(|cx| loop {
+            let mut failed = false;
+            let mut i = 0;
+            while i < attempts.len() {
+                match attempts[i].as_mut().poll(cx) {
+                    Poll::Ready(Ok(stream)) => return Poll::Ready(Ok(stream)),
+                    Poll::Ready(Err(err)) => {
+                        drop(attempts.swap_remove(i));
+                        last_err = Some(err);
+                        failed = true;
+                    }
+                    Poll::Pending => i += 1,
+                }
+            }
+
+            let elapsed = delay
+                .as_mut()
+                .is_some_and(|delay| Pin::new(delay).poll(cx).is_ready());
+            if !attempts.is_empty() && !failed && !elapsed {
+                return Poll::Pending;
+            }
+            match pending.next() {
+                Some(addr) => {
+                    attempts.push(Box::pin(Self::connect(reactor, addr)));
+                    delay = Some(Timer::after(reactor, CONNECTION_ATTEMPT_DELAY));
+                }
+                None if attempts.is_empty() => {
+                    return Poll::Ready(Err(last_err.take().unwrap_or_else(|| {
+                        io::Error::new(io::ErrorKind::InvalidInput, "no addresses to connect to")
+                    })))
+                }
+                None => {
+                    delay = None;
+                    return Poll::Pending;
+                }
+            }
+        })
+        .await
+    }
+
+    /// Wrap a connected socket and its streams.
+    pub(super) fn from_parts(
+        socket: TcpSocket,
+        input: WasiInputStream,
+        output: WasiOutputStream,
+        reactor: &Reactor,
+    ) -> Self {
+        Self {
+            input: InputStream::new(input, reactor),
+            output: OutputStream::new(output, reactor),
+            socket,
+        }
+    }
+
+    /// Get the address of the remote end of the connection.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        let addr = self.socket.remote_address().map_err(socket_error)?;
+        Ok(from_wasi_addr(addr))
+    }
+
+    /// Get the local address of the connection.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        let addr = self.socket.local_address().map_err(socket_error)?;
+        Ok(from_wasi_addr(addr))
+    }
+
+    /// Shut down the writing half of the connection.
+    ///
+    /// This closes the output stream and signals the end of the data to the
+    /// peer, while the connection can still be read from until the peer
+    /// closes its end too. Any later writes fail with
+    /// [`io::ErrorKind::BrokenPipe`]. Bytes which haven't been flushed yet
+    /// may be lost, so flush the stream first.
+    pub fn shutdown_write(&self) -> io::Result<()> {
+        self.socket
+            .shutdown(ShutdownType::Send)
+            .map_err(socket_error)
+    }
+
+    /// Enable or disable `TCP_NODELAY`, which turns off Nagle's algorithm.
+    ///
+    /// WASI has no such option: `wasi:sockets` doesn't let guests change
+    /// it, so this always fails with [`io::ErrorKind::Unsupported`] and
+    /// `_nodelay` is ignored.
+    pub fn set_nodelay(&self, _nodelay: bool) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "TCP_NODELAY is not supported by `wasi:sockets`",
+        ))
+    }
+
+    /// Get whether `TCP_NODELAY` is enabled.
+    ///
+    /// Always fails with [`io::ErrorKind::Unsupported`]; see
+    /// [`TcpStream::set_nodelay`].
+    pub fn nodelay(&self) -> io::Result<bool> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "TCP_NODELAY is not supported by `wasi:sockets`",
+        ))
+    }
+
+    /// Enable keepalive probes once the connection has been idle for `idle`,
+    /// or disable them with `None`.
+    ///
+    /// Fails with [`io::ErrorKind::Unsupported`] if the host doesn't support
+    /// keepalive, and with [`io::ErrorKind::InvalidInput`] if `idle` is zero.
+    pub fn set_keepalive(&self, idle: Option<Duration>) -> io::Result<()> {
+        let Some(idle) = idle else {
+            return self
+                .socket
+                .set_keep_alive_enabled(false)
+                .map_err(socket_error);
+        };
+        let nanos = u64::try_from(idle.as_nanos()).unwrap_or(u64::MAX);
+        self.socket
+            .set_keep_alive_idle_time(nanos)
+            .map_err(socket_error)?;
+        self.socket
+            .set_keep_alive_enabled(true)
+            .map_err(socket_error)
+    }
+
+    /// Get how long the connection has to be idle before keepalive probes are
+    /// sent, or `None` if keepalive is disabled.
+    pub fn keepalive(&self) -> io::Result<Option<Duration>> {
+        if !self.socket.keep_alive_enabled().map_err(socket_error)? {
+            return Ok(None);
+        }
+        let nanos = self.socket.keep_alive_idle_time().map_err(socket_error)?;
+        Ok(Some(Duration::from_nanos(nanos)))
+    }
+
+    /// Get the stream for reading from the connection.
+    pub fn input(&mut self) -> &mut InputStream {
+        &mut self.input
+    }
+
+    /// Get the stream for writing to the connection.
+    pub fn output(&mut self) -> &mut OutputStream {
+        &mut self.output
+    }
+}
+
+/// Order addresses so the address families alternate, starting with the
+/// family of the first address.
+fn interleave(addrs: &[SocketAddr]) -> Vec<SocketAddr> {
+    let Some(first) = addrs.first() else {
+        return Vec::new();
+    };
+    let (preferred, other): (Vec<_>, Vec<_>) = addrs
+        .iter()
+        .copied()
+        .partition(|addr| addr.is_ipv4() == first.is_ipv4());
+    let mut addrs = Vec::with_capacity(preferred.len() + other.len());
+    let (mut preferred, mut other) = (preferred.into_iter(), other.into_iter());
+    loop {
+        match (preferred.next(), other.next()) {
+            (None, None) => return addrs,
+            (a, b) => addrs.extend(a.into_iter().chain(b)),
+        }
+    }
+}
+
+impl AsyncRead for TcpStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().input).poll_read(cx, buf)
+    }
+}
+
+impl AsyncBufRead for TcpStream {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        Pin::new(&mut self.get_mut().input).poll_fill_buf(cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        Pin::new(&mut self.get_mut().input).consume(amt)
+    }
+}
+
+impl AsyncWrite for TcpStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().output).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().output).poll_write_vectored(cx, bufs)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().output).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().output).poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interleave_alternates_families() {
+        let addrs: Vec<SocketAddr> = [
+            "[::1]:80",
+            "[::2]:80",
+            "[::3]:80",
+            "127.0.0.1:80",
+            "127.0.0.2:80",
+        ]
+        .map(|addr| addr.parse().unwrap())
+        .into();
+        let expected: Vec<SocketAddr> = [
+            "[::1]:80",
+            "127.0.0.1:80",
+            "[::2]:80",
+            "127.0.0.2:80",
+            "[::3]:80",
+        ]
+        .map(|addr| addr.parse().unwrap())
+        .into();
+        assert_eq!(interleave(&addrs), expected);
+    }
+
+    #[test]
+    fn interleave_keeps_single_family_order() {
+        let addrs: Vec<SocketAddr> = ["10.0.0.2:443", "10.0.0.1:443"]
+            .map(|addr| addr.parse().unwrap())
+            .into();
+        assert_eq!(interleave(&addrs), addrs);
+        assert!(interleave(&[]).is_empty());
+    }
+}
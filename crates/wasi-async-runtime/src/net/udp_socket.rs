@@ -0,0 +1,104 @@
+use super::{family, finish, from_wasi_addr, socket_error, to_wasi_addr};
+use crate::reactor::PollHandle;
+use crate::Reactor;
+
+use core::future;
+use std::io;
+use std::net::SocketAddr;
+use wasi::sockets::instance_network::instance_network;
+use wasi::sockets::udp::{
+    IncomingDatagramStream, OutgoingDatagram, OutgoingDatagramStream, UdpSocket as WasiUdpSocket,
+};
+use wasi::sockets::udp_create_socket::create_udp_socket;
+
+/// A UDP socket.
+///
+/// Every call to [`UdpSocket::recv_from`] receives exactly one datagram, so
+/// datagram boundaries are preserved.
+#[derive(Debug)]
+pub struct UdpSocket {
+    // IMPORTANT: the order of these fields here matters. The handles hold
+    // pollables which are children of the streams, which in turn are children
+    // of `socket`, so they must be dropped in this order.
+    incoming_handle: PollHandle,
+    outgoing_handle: PollHandle,
+    incoming: IncomingDatagramStream,
+    outgoing: OutgoingDatagramStream,
+    socket: WasiUdpSocket,
+}
+
+impl UdpSocket {
+    /// Bind a socket to `addr`.
+    ///
+    /// Binding to port `0` picks an ephemeral port, which can be obtained
+    /// through [`UdpSocket::local_addr`].
+    pub async fn bind(reactor: &Reactor, addr: SocketAddr) -> io::Result<Self> {
+        let network = instance_network();
+        let socket = create_udp_socket(family(&addr)).map_err(socket_error)?;
+        socket
+            .start_bind(&network, to_wasi_addr(addr))
+            .map_err(socket_error)?;
+        finish(reactor, socket.subscribe(), || socket.finish_bind()).await?;
+
+        // Don't fix the remote address, so we can send to and receive from
+        // any address.
+        let (incoming, outgoing) = socket.stream(None).map_err(socket_error)?;
+        Ok(Self {
+            incoming_handle: reactor.register(incoming.subscribe()),
+            outgoing_handle: reactor.register(outgoing.subscribe()),
+            incoming,
+            outgoing,
+            socket,
+        })
+    }
+
+    /// Get the local address the socket is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        let addr = self.socket.local_address().map_err(socket_error)?;
+        Ok(from_wasi_addr(addr))
+    }
+
+    /// Receive a single datagram, returning its length and the address it
+    /// was sent from.
+    ///
+    /// If the datagram doesn't fit into `buf` only the start of it is copied,
+    /// and the rest is discarded. The returned length is always the length
+    /// of the whole datagram, so a length greater than `buf.len()` signals it
+    /// was truncated.
+    pub async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        loop {
+            let mut datagrams = self.incoming.receive(1).map_err(socket_error)?;
+            match datagrams.pop() {
+                Some(datagram) => {
+                    let len = datagram.data.len().min(buf.len());
+                    buf[..len].copy_from_slice(&datagram.data[..len]);
+                    let addr = from_wasi_addr(datagram.remote_address);
+                    return Ok((datagram.data.len(), addr));
+                }
+                None => future::poll_fn(|cx| self.incoming_handle.poll(cx)).await,
+            }
+        }
+    }
+
+    /// Send `buf` as a single datagram to `addr`, returning the number of
+    /// bytes sent.
+    pub async fn send_to(&self, buf: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        let datagram = OutgoingDatagram {
+            data: buf.to_vec(),
+            remote_address: Some(to_wasi_addr(addr)),
+        };
+        loop {
+            // The stream only accepts datagrams once `check-send` permits it
+            if self.outgoing.check_send().map_err(socket_error)? > 0 {
+                let sent = self
+                    .outgoing
+                    .send(core::slice::from_ref(&datagram))
+                    .map_err(socket_error)?;
+                if sent == 1 {
+                    return Ok(buf.len());
+                }
+            }
+            future::poll_fn(|cx| self.outgoing_handle.poll(cx)).await;
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! A cell which is initialized once, asynchronously.
+
+use alloc::vec::Vec;
+use core::cell::{Cell, OnceCell as SyncOnceCell, RefCell};
+use core::fmt;
+use core::future::{self, Future};
+use core::task::{Poll, Waker};
+
+/// A cell whose value is initialized once, by an async initializer.
+///
+/// The first task to call [`OnceCell::get_or_init`] runs the initializer,
+/// while other tasks calling it in the meantime are parked until the value
+/// is ready. Since the runtime is single-threaded, the cell is meant to be
+/// shared through an [`Rc`](alloc::rc::Rc) rather than an `Arc`.
+pub struct OnceCell<T> {
+    value: SyncOnceCell<T>,
+    /// Whether an initializer is currently running.
+    initializing: Cell<bool>,
+    waiters: RefCell<Vec<Waker>>,
+}
+
+impl<T> OnceCell<T> {
+    /// Create a new, uninitialized cell.
+    pub fn new() -> Self {
+        Self {
+            value: SyncOnceCell::new(),
+            initializing: Cell::new(false),
+            waiters: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Get the value, if the cell has been initialized.
+    pub fn get(&self) -> Option<&T> {
+        self.value.get()
+    }
+
+    /// Get the value, initializing the cell with `init` if it hasn't been.
+    ///
+    /// If another task is already initializing the cell, this waits for it
+    /// to finish rather than running `init`. If the future running the
+    /// initializer is dropped before it completes, the cell stays
+    /// uninitialized and one of the waiting tasks runs its own initializer
+    /// instead.
+    pub async fn get_or_init<F, Fut>(&self, init: F) -> &T
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        // Wait until the cell is either initialized or free to initialize
+        future::poll_fn(|cx| {
+            if self.value.get().is_some() || !self.initializing.get() {
+                return Poll::Ready(());
+            }
+            let mut waiters = self.waiters.borrow_mut();
+            if !waiters.iter().any(|waker| waker.will_wake(cx.waker())) {
+                waiters.push(cx.waker().clone());
+            }
+            Poll::Pending
+        })
+        .await;
+        if let Some(value) = self.value.get() {
+            return value;
+        }
+
+        self.initializing.set(true);
+        let guard = Initializing { cell: self };
+        let value = init().await;
+        let _ = self.value.set(value);
+        drop(guard);
+
+        // NOTE: nobody else can set the value while we're initializing it
+        self.value.get().unwrap()
+    }
+
+    /// Take the value out of the cell, if it has been initialized.
+    pub fn into_inner(self) -> Option<T> {
+        self.value.into_inner()
+    }
+}
+
+impl<T> Default for OnceCell<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for OnceCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OnceCell")
+            .field("value", &self.value.get())
+            .field("initializing", &self.initializing.get())
+            .finish()
+    }
+}
+
+/// Marks the cell as no longer initializing once dropped, whether the
+/// initializer completed or was cancelled, and wakes the waiting tasks.
+struct Initializing<'a, T> {
+    cell: &'a OnceCell<T>,
+}
+
+impl<T> Drop for Initializing<'_, T> {
+    fn drop(&mut self) {
+        self.cell.initializing.set(false);
+        for waker in self.cell.waiters.take() {
+            waker.wake();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::rc::Rc;
+    use core::cell::Cell;
+    use futures_lite::future::yield_now;
+
+    use super::*;
+
+    #[test]
+    fn initializes_once() {
+        let runs = Rc::new(Cell::new(0));
+        crate::block_on(|reactor| async move {
+            let cell = Rc::new(OnceCell::new());
+            let handles: Vec<_> = (0..2)
+                .map(|_| {
+                    let (cell, runs) = (cell.clone(), runs.clone());
+                    reactor.spawn(async move {
+                        let value = cell
+                            .get_or_init(|| async {
+                                runs.set(runs.get() + 1);
+                                // Give the other task a chance to race us
+                                yield_now().await;
+                                7
+                            })
+                            .await;
+                        *value
+                    })
+                })
+                .collect();
+            for handle in handles {
+                assert_eq!(handle.await, 7);
+            }
+            assert_eq!(runs.get(), 1);
+        });
+    }
+
+    #[test]
+    fn dropped_initializer_leaves_cell_empty() {
+        crate::block_on(|_| async {
+            let cell = OnceCell::new();
+            {
+                let mut init = core::pin::pin!(cell.get_or_init(|| async {
+                    yield_now().await;
+                    1
+                }));
+                let pending = future::poll_fn(|cx| Poll::Ready(init.as_mut().poll(cx))).await;
+                assert!(pending.is_pending());
+            }
+            assert!(cell.get().is_none());
+            assert_eq!(*cell.get_or_init(|| async { 2 }).await, 2);
+            assert_eq!(cell.into_inner(), Some(2));
+        });
+    }
+}
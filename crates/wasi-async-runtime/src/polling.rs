@@ -44,6 +44,13 @@ impl Poller {
         self.targets.try_remove(key.0 as usize)
     }
 
+    /// Remove and drop all `Pollable`s, releasing the memory used to track
+    /// them.
+    pub(crate) fn clear(&mut self) {
+        self.targets.clear();
+        self.targets.shrink_to_fit();
+    }
+
     /// Block the current thread until a new event has triggered.
     ///
     /// This will clear the value of `ready_list`.
@@ -61,12 +68,6 @@ impl Poller {
             targets.push(target);
         }
 
-        debug_assert_ne!(
-            targets.len(),
-            0,
-            "Attempting to block on an empty list of pollables - without any pending work, no progress can be made and the program may spin indefinitely"
-        );
-
         // Now that we have that association, we're ready to poll our targets.
         // This will block until an event has completed.
         let ready_indexes = poll(&targets);
@@ -1,28 +1,74 @@
 use super::polling::{EventKey, Poller};
+use super::task::{JoinHandle, Task};
+use super::{Deadline, Timer};
 
 use alloc::rc::Rc;
+use alloc::vec::Vec;
 use core::cell::RefCell;
-use core::future;
-use core::task::Poll;
+use core::future::{self, Future};
+use core::pin::Pin;
 use core::task::Waker;
+use core::task::{Context, Poll};
+use core::time::Duration;
 #[cfg(not(feature = "std"))]
 use hashbrown::HashMap;
 #[cfg(feature = "std")]
 use std::collections::HashMap;
 use wasi::io::poll::Pollable;
 
+#[cfg(feature = "std")]
+std::thread_local! {
+    /// The reactor of the innermost running `block_on`.
+    static CURRENT: RefCell<Option<Reactor>> = const { RefCell::new(None) };
+}
+
 /// Manage async system resources for WASI 0.2
 #[derive(Debug, Clone)]
 pub struct Reactor {
     inner: Rc<RefCell<InnerReactor>>,
 }
 
+/// Restores the previously current reactor once dropped, so nested event
+/// loops each see their own reactor.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub(crate) struct EnterGuard {
+    previous: Option<Reactor>,
+}
+
+#[cfg(feature = "std")]
+impl Drop for EnterGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+/// Counters describing the state of a [`Reactor`], returned by
+/// [`Reactor::stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct ReactorStats {
+    /// The number of pollables registered with the reactor.
+    pub pollables: usize,
+    /// The number of wakers waiting on the registered pollables.
+    pub wakers: usize,
+    /// The number of times the reactor blocked waiting for events.
+    pub turns: u64,
+}
+
 /// The private, internal `Reactor` implementation - factored out so we can take
 /// a lock of the whole.
 #[derive(Debug)]
 struct InnerReactor {
     poller: Poller,
     wakers: HashMap<EventKey, Waker>,
+    spawned: Vec<Task>,
+    /// Bumped on every call to `Reactor::clear`, so handles from before the
+    /// clear can tell their key may have been reused.
+    generation: u64,
+    /// The number of times the reactor blocked waiting for events.
+    turns: u64,
 }
 
 impl Reactor {
@@ -32,59 +78,383 @@ impl Reactor {
             inner: Rc::new(RefCell::new(InnerReactor {
                 poller: Poller::new(),
                 wakers: HashMap::new(),
+                spawned: Vec::new(),
+                generation: 0,
+                turns: 0,
             })),
         }
     }
 
+    /// Get the reactor of the running event loop.
+    ///
+    /// This allows code running inside [`block_on`](crate::block_on) to use
+    /// the reactor without having it passed in.
+    ///
+    /// # Panics
+    ///
+    /// Panics when called outside of `block_on`. See [`Reactor::try_current`]
+    /// for a version which doesn't panic.
+    #[cfg(feature = "std")]
+    pub fn current() -> Self {
+        Self::try_current().expect("`Reactor::current` called outside of `block_on`")
+    }
+
+    /// Get the reactor of the running event loop, or `None` when called
+    /// outside of [`block_on`](crate::block_on).
+    #[cfg(feature = "std")]
+    pub fn try_current() -> Option<Self> {
+        CURRENT.with(|current| current.borrow().clone())
+    }
+
+    /// Make this the reactor returned by [`Reactor::current`], until the
+    /// returned guard is dropped.
+    #[cfg(feature = "std")]
+    pub(crate) fn enter(&self) -> EnterGuard {
+        let previous = CURRENT.with(|current| current.replace(Some(self.clone())));
+        EnterGuard { previous }
+    }
+
     /// Block until new events are ready. Calls the respective wakers once done.
     ///
     /// # On Wakers and single-threaded runtimes
     ///
     /// At first glance it might seem silly that this goes through the motions
-    /// of calling the wakers. The main waker we create here is a `noop` waker:
-    /// it does nothing. However, it is common and encouraged to use wakers to
-    /// distinguish between events. Concurrency primitives may construct their
-    /// own wakers to keep track of identity and wake more precisely. We do not
-    /// control the wakers construted by other libraries, and it is for this
-    /// reason that we have to call all the wakers - even if by default they
-    /// will do nothing.
+    /// of calling the wakers. The wakers we create in `block_on` only mark
+    /// their task as ready to be polled again. However, it is common and
+    /// encouraged to use wakers to distinguish between events. Concurrency
+    /// primitives may construct their own wakers to keep track of identity
+    /// and wake more precisely. We do not control the wakers constructed by
+    /// other libraries, and it is for this reason that we have to call all
+    /// the wakers - even if by default they will do nothing.
+    ///
+    /// The ready wakers are called in the order of their keys, starting from
+    /// an offset which moves along every turn. That way wakers which act on
+    /// being woken right away, rather than flagging a task, get to do so
+    /// first in turn.
+    ///
+    /// Keys without a registered waker are skipped: that happens when a
+    /// pollable was registered but never polled, which is not an error.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no pollables are registered. `block_on` only blocks when
+    /// none of its futures are ready, so with nothing registered that could
+    /// wake them up, they would never make progress again.
     pub(crate) fn block_until(&self) {
         let mut reactor = self.inner.borrow_mut();
-        for key in reactor.poller.block_until() {
-            match reactor.wakers.get(&key) {
-                Some(waker) => waker.wake_by_ref(),
-                None => panic!("tried to wake the waker for non-existent `{key:?}`"),
+        assert!(
+            !reactor.poller.targets.is_empty(),
+            "runtime deadlock: no pollables registered and future not ready"
+        );
+        reactor.turns += 1;
+        let mut ready = reactor.poller.block_until();
+
+        rotate(&mut ready, reactor.turns);
+        for key in ready {
+            if let Some(waker) = reactor.wakers.get(&key) {
+                waker.wake_by_ref();
             }
         }
     }
 
+    /// Spawn a future onto the reactor, running it concurrently with the
+    /// future passed to [`block_on`](crate::block_on).
+    ///
+    /// The returned [`JoinHandle`] resolves to the output of the future.
+    /// Once the future passed to `block_on` resolves, spawned tasks keep
+    /// running until they complete, unless the event loop was started with
+    /// [`block_on_abort`](crate::block_on_abort).
+    ///
+    /// With the `std` feature, a task which panics is dropped without
+    /// affecting the other tasks, and the panic is resumed by its
+    /// `JoinHandle`.
+    pub fn spawn<F>(&self, fut: F) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        let (task, handle) = Task::new(fut);
+        self.inner.borrow_mut().spawned.push(task);
+        handle
+    }
+
+    /// Take all tasks spawned since the last call.
+    pub(crate) fn take_spawned(&self) -> Vec<Task> {
+        core::mem::take(&mut self.inner.borrow_mut().spawned)
+    }
+
+    /// Whether tasks have been spawned since the last call to `take_spawned`.
+    pub(crate) fn has_spawned(&self) -> bool {
+        !self.inner.borrow().spawned.is_empty()
+    }
+
+    /// Whether any pollables are registered with the reactor.
+    pub(crate) fn has_pollables(&self) -> bool {
+        !self.inner.borrow().poller.targets.is_empty()
+    }
+
+    /// Get counters describing the state of the reactor, for diagnostics.
+    pub fn stats(&self) -> ReactorStats {
+        let reactor = self.inner.borrow();
+        ReactorStats {
+            pollables: reactor.poller.targets.len(),
+            wakers: reactor.wakers.len(),
+            turns: reactor.turns,
+        }
+    }
+
+    /// Register a `Pollable` with the reactor.
+    ///
+    /// The pollable is deregistered again once the returned handle is
+    /// dropped.
+    pub fn register(&self, pollable: Pollable) -> PollHandle {
+        let mut reactor = self.inner.borrow_mut();
+        PollHandle {
+            key: reactor.poller.insert(pollable),
+            generation: reactor.generation,
+            reactor: self.clone(),
+        }
+    }
+
+    /// Register several `Pollable`s with the reactor at once.
+    ///
+    /// This takes the lock on the reactor only once, and returns the handles
+    /// in the same order as the pollables. Each handle deregisters its own
+    /// pollable once dropped.
+    pub fn register_many(&self, pollables: Vec<Pollable>) -> Vec<PollHandle> {
+        let mut reactor = self.inner.borrow_mut();
+        pollables
+            .into_iter()
+            .map(|pollable| PollHandle {
+                key: reactor.poller.insert(pollable),
+                generation: reactor.generation,
+                reactor: self.clone(),
+            })
+            .collect()
+    }
+
+    /// Deregister and drop all pollables, along with their wakers.
+    ///
+    /// This resets the reactor after a burst of activity, such as many
+    /// connections being opened at once. Any outstanding [`PollHandle`]s
+    /// become no-ops: they no longer resolve, and dropping them does nothing.
+    /// Futures waiting on them will stay pending forever.
+    pub fn clear(&self) {
+        let mut reactor = self.inner.borrow_mut();
+        reactor.poller.clear();
+        reactor.wakers.clear();
+        reactor.wakers.shrink_to_fit();
+        reactor.generation += 1;
+    }
+
     /// Wait for the pollable to resolve.
     pub async fn wait_for(&self, pollable: Pollable) {
-        let mut pollable = Some(pollable);
-        let mut key = None;
+        let handle = self.register(pollable);
+        future::poll_fn(|cx| handle.poll(cx)).await
+    }
 
-        // This function is the core loop of our function; it will be called
-        // multiple times as the future is resolving.
+    /// Wait for the first of several pollables to resolve.
+    ///
+    /// Resolves to the index of the pollable which became ready. If several
+    /// are ready at once, the lowest index is reported. All pollables are
+    /// deregistered and dropped once this resolves, so to keep waiting on the
+    /// others, subscribe to their resources again.
+    ///
+    /// Panics if `pollables` is empty, since there would be nothing to wait
+    /// for.
+    pub async fn wait_for_any(&self, pollables: Vec<Pollable>) -> usize {
+        assert!(
+            !pollables.is_empty(),
+            "`wait_for_any` was called without any pollables"
+        );
+        let handles = self.register_many(pollables);
+
+        // If none of the handles are ready, every one of them has been polled
+        // and has registered the waker.
+        future::poll_fn(
+            |cx| match handles.iter().position(|h| h.poll(cx).is_ready()) {
+                Some(index) => Poll::Ready(index),
+                None => Poll::Pending,
+            },
+        )
+        .await
+    }
+
+    /// Wait until `dur` has elapsed.
+    pub async fn sleep(&self, dur: Duration) {
+        Timer::after(self, dur).await
+    }
+
+    /// Wait for the pollable to resolve, giving up once `dur` has elapsed.
+    ///
+    /// Returns `true` if the pollable resolved, and `false` if the timeout
+    /// was hit first.
+    pub async fn wait_for_with_timeout(&self, pollable: Pollable, dur: Duration) -> bool {
+        let handle = self.register(pollable);
+        let mut timeout = Timer::after(self, dur);
+
+        // Both handles are dropped once this resolves, which removes them from
+        // the reactor regardless of which one won.
         future::poll_fn(|cx| {
-            // Start by taking a lock on the reactor. This is single-threaded
-            // and short-lived, so it will never be contended.
-            let mut reactor = self.inner.borrow_mut();
-
-            // Schedule interest in the `pollable` on the first iteration. On
-            // every iteration, register the waker with the reactor.
-            let key = key.get_or_insert_with(|| reactor.poller.insert(pollable.take().unwrap()));
-            reactor.wakers.insert(*key, cx.waker().clone());
-
-            // Check whether we're ready or need to keep waiting. If we're
-            // ready, we clean up after ourselves.
-            if reactor.poller.get(key).unwrap().ready() {
-                reactor.poller.remove(*key);
-                reactor.wakers.remove(key);
-                Poll::Ready(())
+            if handle.poll(cx).is_ready() {
+                Poll::Ready(true)
+            } else if Pin::new(&mut timeout).poll(cx).is_ready() {
+                Poll::Ready(false)
             } else {
                 Poll::Pending
             }
         })
         .await
     }
+
+    /// Wait for the pollable to resolve, giving up once `deadline` has
+    /// passed.
+    ///
+    /// Returns `true` if the pollable resolved, and `false` if the deadline
+    /// was hit first.
+    pub async fn wait_for_until(&self, pollable: Pollable, deadline: Deadline) -> bool {
+        let handle = self.register(pollable);
+        let mut timeout = deadline.timer(self);
+        future::poll_fn(|cx| {
+            if handle.poll(cx).is_ready() {
+                Poll::Ready(true)
+            } else if Pin::new(&mut timeout).poll(cx).is_ready() {
+                Poll::Ready(false)
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+/// A `Pollable` registered with the reactor.
+///
+/// Dropping the handle removes both the pollable and its waker from the
+/// reactor.
+#[derive(Debug)]
+pub struct PollHandle {
+    key: EventKey,
+    generation: u64,
+    reactor: Reactor,
+}
+
+impl PollHandle {
+    /// Check whether the pollable is ready, without registering a waker.
+    ///
+    /// Like [`PollHandle::poll`], a handle whose pollable is no longer
+    /// registered is never ready.
+    pub fn is_ready(&self) -> bool {
+        let reactor = self.reactor.inner.borrow();
+        reactor.generation == self.generation
+            && reactor
+                .poller
+                .get(&self.key)
+                .is_some_and(|pollable| pollable.ready())
+    }
+
+    /// Wait until the pollable is ready.
+    ///
+    /// Unlike [`Reactor::wait_for`], this keeps the pollable registered, so
+    /// the handle can be waited on again. Each poll of the returned future
+    /// goes through [`PollHandle::poll`].
+    pub async fn ready(&self) {
+        future::poll_fn(|cx| self.poll(cx)).await
+    }
+
+    /// Check whether the pollable is ready, registering the waker to be
+    /// called once it is if not.
+    ///
+    /// A handle whose pollable is no longer registered, for example after a
+    /// call to [`Reactor::clear`], is never ready.
+    pub fn poll(&self, cx: &mut Context<'_>) -> Poll<()> {
+        // Start by taking a lock on the reactor. This is single-threaded
+        // and short-lived, so it will never be contended.
+        let mut reactor = self.reactor.inner.borrow_mut();
+
+        // The reactor was cleared since we registered, so our key either no
+        // longer resolves or belongs to someone else now.
+        if reactor.generation != self.generation {
+            return Poll::Pending;
+        }
+
+        // If the pollable was removed out from under us there is nothing left
+        // to wait on. We report `Pending` rather than `Ready`: callers such as
+        // stream readers retry their operation when we resolve, and would spin
+        // forever on a pollable that can never fire. We also don't register
+        // the waker, since that would leak it into the reactor, or clobber the
+        // waker of whoever the key is handed out to next.
+        let Some(pollable) = reactor.poller.get(&self.key) else {
+            return Poll::Pending;
+        };
+        let ready = pollable.ready();
+
+        // On every iteration, register the waker with the reactor.
+        reactor.wakers.insert(self.key, cx.waker().clone());
+
+        // Check whether we're ready or need to keep waiting.
+        if ready {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for PollHandle {
+    fn drop(&mut self) {
+        let mut reactor = self.reactor.inner.borrow_mut();
+        if reactor.generation == self.generation {
+            reactor.poller.remove(self.key);
+            reactor.wakers.remove(&self.key);
+        }
+    }
+}
+
+/// Rotate the ready keys so waking starts from a different position every
+/// turn, and pollables later in the list aren't always woken last.
+fn rotate<T>(keys: &mut [T], turn: u64) {
+    if !keys.is_empty() {
+        let offset = (turn % keys.len() as u64) as usize;
+        keys.rotate_left(offset);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn current_is_the_running_reactor() {
+        assert!(Reactor::try_current().is_none());
+        crate::block_on(|reactor| async move {
+            let current = Reactor::current();
+            assert!(Rc::ptr_eq(&current.inner, &reactor.inner));
+        });
+        assert!(Reactor::try_current().is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "without any pollables")]
+    fn wait_for_any_rejects_empty_list() {
+        crate::block_on(|reactor| async move { reactor.wait_for_any(Vec::new()).await });
+    }
+
+    #[test]
+    fn rotate_starts_at_a_different_key_every_turn() {
+        let first: Vec<_> = (0..6)
+            .map(|turn| {
+                let mut keys = [1, 2, 3];
+                rotate(&mut keys, turn);
+                keys[0]
+            })
+            .collect();
+        assert_eq!(first, [1, 2, 3, 1, 2, 3]);
+
+        let mut keys = [1, 2, 3];
+        rotate(&mut keys, 2);
+        assert_eq!(keys, [3, 1, 2]);
+        rotate(&mut [0; 0], 5);
+    }
 }
@@ -0,0 +1,27 @@
+//! Async access to the standard streams over `wasi:cli`.
+//!
+//! The host hands out the same standard streams on every call, so each
+//! function here wraps a new handle to the same stream. Since an
+//! [`InputStream`] buffers what it reads, bytes buffered by one stream over
+//! stdin are never seen by another: create one and keep it around rather
+//! than calling [`stdin`] for every read. Likewise, bytes buffered by an
+//! [`OutputStream`] are only written out once it is flushed.
+
+use crate::{InputStream, OutputStream, Reactor};
+
+use wasi::cli::{stderr::get_stderr, stdin::get_stdin, stdout::get_stdout};
+
+/// Get an input stream reading from stdin.
+pub fn stdin(reactor: &Reactor) -> InputStream {
+    InputStream::new(get_stdin(), reactor)
+}
+
+/// Get an output stream writing to stdout.
+pub fn stdout(reactor: &Reactor) -> OutputStream {
+    OutputStream::new(get_stdout(), reactor)
+}
+
+/// Get an output stream writing to stderr.
+pub fn stderr(reactor: &Reactor) -> OutputStream {
+    OutputStream::new(get_stderr(), reactor)
+}
@@ -0,0 +1,1025 @@
+//! Async wrappers around the WASI I/O streams.
+
+use super::error::{read_error, write_error};
+use super::fs::fs_error;
+use super::reactor::PollHandle;
+use super::Reactor;
+
+use core::future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use futures_lite::{ready, AsyncBufRead, AsyncRead, AsyncSeek, AsyncWrite, Stream};
+use std::io::{self, IoSlice, SeekFrom};
+use wasi::filesystem::types::{Descriptor, DescriptorType};
+use wasi::io::streams::{
+    InputStream as WasiInputStream, OutputStream as WasiOutputStream,
+    StreamError as WasiStreamError,
+};
+
+/// The maximum number of bytes requested from the underlying stream per read.
+const DEFAULT_CAPACITY: usize = 8 * 1024;
+
+/// The minimum number of bytes requested from the underlying stream per
+/// read, unless the capacity is smaller.
+const MIN_READ_SIZE: usize = 512;
+
+/// An async wrapper around a WASI input stream.
+///
+/// Implements [`AsyncRead`] and [`AsyncBufRead`], waking the task through the
+/// reactor once the stream has more data available.
+#[derive(Debug)]
+pub struct InputStream {
+    // IMPORTANT: the order of these fields here matters. `handle` holds a
+    // pollable which is a child of `inner`, so it must be dropped first.
+    handle: PollHandle,
+    inner: WasiInputStream,
+    buf: ReadBuf,
+    capacity: usize,
+    /// The number of bytes requested per read, adapted to how much data the
+    /// stream has available.
+    read_size: usize,
+    /// Whether the last read filled up the whole read size.
+    last_read_full: bool,
+}
+
+impl InputStream {
+    /// Wrap a WASI input stream.
+    pub fn new(inner: WasiInputStream, reactor: &Reactor) -> Self {
+        Self::with_capacity(inner, reactor, DEFAULT_CAPACITY)
+    }
+
+    /// Wrap a WASI input stream, requesting at most `capacity` bytes per
+    /// read.
+    ///
+    /// The number of bytes requested per read adapts to the stream: it grows
+    /// after consecutive reads fill it up completely, and shrinks again after
+    /// reads which return much less, staying within `capacity`. A `capacity`
+    /// of `0` is treated as `1`.
+    pub fn with_capacity(inner: WasiInputStream, reactor: &Reactor, capacity: usize) -> Self {
+        let handle = reactor.register(inner.subscribe());
+        let capacity = capacity.max(1);
+        Self {
+            handle,
+            inner,
+            buf: ReadBuf::default(),
+            capacity,
+            read_size: MIN_READ_SIZE.min(capacity),
+            last_read_full: false,
+        }
+    }
+
+    /// Unwrap the WASI input stream, deregistering it from the reactor.
+    ///
+    /// Any bytes which were buffered but not yet read are lost.
+    pub fn into_inner(self) -> WasiInputStream {
+        // The handle's pollable is a child of the stream, so it has to be
+        // dropped before the stream can be handed out.
+        let Self { handle, inner, .. } = self;
+        drop(handle);
+        inner
+    }
+
+    /// Get the number of bytes currently requested per read.
+    pub fn read_size(&self) -> usize {
+        self.read_size
+    }
+
+    /// Adapt the read size to the number of bytes the last read returned.
+    fn adapt_read_size(&mut self, len: usize) {
+        let full = len >= self.read_size;
+        self.read_size = next_read_size(self.read_size, len, self.last_read_full, self.capacity);
+        self.last_read_full = full;
+    }
+
+    /// Get the number of bytes which have been read from the underlying
+    /// stream, but not yet consumed.
+    pub fn buffered_len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Discard all bytes which have been read from the underlying stream,
+    /// but not yet consumed.
+    pub fn clear_buffer(&mut self) {
+        self.buf.clear();
+    }
+
+    /// Read bytes without waiting for the stream to become ready.
+    ///
+    /// Returns buffered bytes right away if there are any. Otherwise this
+    /// reads from the underlying stream once if it's ready, and fails with
+    /// [`io::ErrorKind::WouldBlock`] if it isn't or had nothing to read.
+    /// Returns `Ok(0)` once the stream has been closed.
+    pub fn try_read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.buffered_len() == 0 {
+            if !self.handle.is_ready() {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            match self.inner.read(self.read_size as u64) {
+                Ok(bytes) if bytes.is_empty() => return Err(io::ErrorKind::WouldBlock.into()),
+                Ok(bytes) => {
+                    self.adapt_read_size(bytes.len());
+                    self.buf.set(bytes);
+                }
+                Err(WasiStreamError::Closed) => return Ok(0),
+                Err(err) => return Err(read_error(err)),
+            }
+        }
+
+        Ok(self.buf.read_into(buf))
+    }
+
+    /// Turn the stream into a [`Stream`] of the chunks read from it.
+    ///
+    /// Each chunk holds the bytes returned by a single read from the
+    /// underlying stream, and the stream ends once it is closed. Any bytes
+    /// which were already buffered are yielded as the first chunk.
+    pub fn into_chunks(self) -> Chunks {
+        Chunks { stream: self }
+    }
+
+    /// Read all bytes until the end of the stream, appending them to `buf`.
+    ///
+    /// Unlike going through [`AsyncRead`], this appends every read from the
+    /// underlying stream directly onto `buf`, requesting as many bytes per
+    /// read as the stream's capacity. Returns the number of bytes read.
+    pub async fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        let start = buf.len();
+
+        // Start with whatever is still buffered
+        buf.extend_from_slice(self.buf.available());
+        self.clear_buffer();
+
+        loop {
+            match self.inner.read(self.capacity as u64) {
+                Ok(bytes) if bytes.is_empty() => {
+                    self.handle.ready().await;
+                }
+                // Move the first chunk instead of copying it
+                Ok(bytes) if buf.is_empty() => *buf = bytes,
+                Ok(bytes) => buf.extend_from_slice(&bytes),
+                Err(WasiStreamError::Closed) => return Ok(buf.len() - start),
+                Err(err) => return Err(read_error(err)),
+            }
+        }
+    }
+
+    /// Read exactly enough bytes to fill `buf`.
+    ///
+    /// Buffered bytes are used first, after which the stream is read from
+    /// until `buf` is full. Fails with [`io::ErrorKind::UnexpectedEof`] if
+    /// the stream closes before then, in which case the contents of `buf`
+    /// are unspecified.
+    pub async fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        read_exact(self, buf).await
+    }
+
+    /// Move all bytes from this stream into `dst`, returning how many were
+    /// moved.
+    ///
+    /// See [`splice`](crate::splice).
+    pub(crate) async fn splice_into(&mut self, dst: &mut OutputStream) -> io::Result<u64> {
+        // Bytes which were already read can't be spliced, so write them first
+        let mut moved = self.buffered_len() as u64;
+        dst.write_all_buffered(self.buf.available()).await?;
+        self.clear_buffer();
+
+        loop {
+            let open = dst.open()?;
+            let permit = match open.inner.check_write().map_err(write_error)? {
+                0 => {
+                    open.handle.ready().await;
+                    continue;
+                }
+                permit => permit,
+            };
+            match open.inner.splice(&self.inner, permit) {
+                // Nothing could be read yet; wait for the source
+                Ok(0) => self.handle.ready().await,
+                Ok(len) => moved += len,
+                // Either side may have closed, so check it wasn't the output
+                Err(WasiStreamError::Closed) => {
+                    open.inner.check_write().map_err(write_error)?;
+                    break;
+                }
+                // The host may not be able to splice these streams, so copy
+                // through a buffer instead. If the streams are broken, this
+                // reports why.
+                Err(WasiStreamError::LastOperationFailed(_)) => {
+                    return Ok(moved + crate::copy(self, dst).await?);
+                }
+            }
+        }
+        future::poll_fn(|cx| Pin::new(&mut *dst).poll_flush(cx)).await?;
+        Ok(moved)
+    }
+
+    /// Look at the next `n` bytes without consuming them.
+    ///
+    /// Reads from the stream until at least `n` bytes are buffered, and
+    /// returns them without advancing: later reads return the same bytes.
+    /// If the stream closes first, the bytes which are left are returned,
+    /// which may be fewer than `n`.
+    pub async fn peek(&mut self, n: usize) -> io::Result<&[u8]> {
+        while self.buf.len() < n {
+            match self
+                .inner
+                .read((n - self.buf.len()).max(self.read_size) as u64)
+            {
+                Ok(bytes) if bytes.is_empty() => self.handle.ready().await,
+                Ok(bytes) => self.buf.extend(&bytes),
+                Err(WasiStreamError::Closed) => break,
+                Err(err) => return Err(read_error(err)),
+            }
+        }
+        Ok(self.buf.first(n))
+    }
+
+    /// Read a single byte.
+    ///
+    /// Like the other integer readers this uses buffered bytes first, and
+    /// fails with [`io::ErrorKind::UnexpectedEof`] if the stream closes
+    /// before enough bytes were read.
+    pub async fn read_u8(&mut self) -> io::Result<u8> {
+        let [byte] = self.read_array().await?;
+        Ok(byte)
+    }
+
+    /// Read a big-endian `u16`.
+    ///
+    /// See [`InputStream::read_u8`].
+    pub async fn read_u16_be(&mut self) -> io::Result<u16> {
+        Ok(u16::from_be_bytes(self.read_array().await?))
+    }
+
+    /// Read a little-endian `u16`.
+    ///
+    /// See [`InputStream::read_u8`].
+    pub async fn read_u16_le(&mut self) -> io::Result<u16> {
+        Ok(u16::from_le_bytes(self.read_array().await?))
+    }
+
+    /// Read a big-endian `u32`.
+    ///
+    /// See [`InputStream::read_u8`].
+    pub async fn read_u32_be(&mut self) -> io::Result<u32> {
+        Ok(u32::from_be_bytes(self.read_array().await?))
+    }
+
+    /// Read a little-endian `u32`.
+    ///
+    /// See [`InputStream::read_u8`].
+    pub async fn read_u32_le(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.read_array().await?))
+    }
+
+    /// Read a big-endian `u64`.
+    ///
+    /// See [`InputStream::read_u8`].
+    pub async fn read_u64_be(&mut self) -> io::Result<u64> {
+        Ok(u64::from_be_bytes(self.read_array().await?))
+    }
+
+    /// Read a little-endian `u64`.
+    ///
+    /// See [`InputStream::read_u8`].
+    pub async fn read_u64_le(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.read_array().await?))
+    }
+
+    /// Read exactly `N` bytes into an array.
+    async fn read_array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        read_array(self).await
+    }
+}
+
+/// The bytes read from a stream which haven't been consumed yet.
+#[derive(Debug, Default)]
+struct ReadBuf {
+    bytes: Vec<u8>,
+    /// The number of bytes at the front of `bytes` which were consumed.
+    pos: usize,
+}
+
+impl ReadBuf {
+    /// Get the number of unconsumed bytes.
+    fn len(&self) -> usize {
+        self.bytes.len() - self.pos
+    }
+
+    /// Whether all bytes have been consumed.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the unconsumed bytes.
+    fn available(&self) -> &[u8] {
+        &self.bytes[self.pos..]
+    }
+
+    /// Get up to the first `n` unconsumed bytes.
+    fn first(&self, n: usize) -> &[u8] {
+        &self.available()[..n.min(self.len())]
+    }
+
+    /// Mark `amt` bytes as consumed.
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.bytes.len());
+    }
+
+    /// Copy as many unconsumed bytes as fit into `buf`, and consume them.
+    fn read_into(&mut self, buf: &mut [u8]) -> usize {
+        let len = self.len().min(buf.len());
+        buf[..len].copy_from_slice(&self.available()[..len]);
+        self.consume(len);
+        len
+    }
+
+    /// Replace the contents with freshly read bytes.
+    fn set(&mut self, bytes: Vec<u8>) {
+        self.bytes = bytes;
+        self.pos = 0;
+    }
+
+    /// Append freshly read bytes after the unconsumed ones, dropping the
+    /// consumed bytes to make room.
+    fn extend(&mut self, bytes: &[u8]) {
+        self.bytes.drain(..self.pos);
+        self.pos = 0;
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    /// Take the unconsumed bytes, leaving the buffer empty.
+    fn take(&mut self) -> Vec<u8> {
+        let mut bytes = core::mem::take(&mut self.bytes);
+        bytes.drain(..core::mem::take(&mut self.pos));
+        bytes
+    }
+
+    /// Drop all unconsumed bytes.
+    fn clear(&mut self) {
+        self.bytes.clear();
+        self.pos = 0;
+    }
+}
+
+impl AsyncRead for InputStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        // There's no room to read into, so don't touch the stream
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let available = match self.as_mut().poll_fill_buf(cx) {
+            Poll::Ready(Ok(available)) => available,
+            Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+            Poll::Pending => return Poll::Pending,
+        };
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Poll::Ready(Ok(len))
+    }
+}
+
+impl AsyncBufRead for InputStream {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+
+        // Only read from the stream once everything buffered has been consumed
+        if !this.buf.is_empty() {
+            return Poll::Ready(Ok(this.buf.available()));
+        }
+
+        loop {
+            match this.inner.read(this.read_size as u64) {
+                // No data is available yet; wait for the stream to be ready
+                // and try again.
+                Ok(bytes) if bytes.is_empty() => {
+                    if this.handle.poll(cx).is_pending() {
+                        return Poll::Pending;
+                    }
+                }
+                Ok(bytes) => {
+                    this.adapt_read_size(bytes.len());
+                    this.buf.set(bytes);
+                    return Poll::Ready(Ok(this.buf.available()));
+                }
+                Err(WasiStreamError::Closed) => return Poll::Ready(Ok(&[])),
+                Err(err) => return Poll::Ready(Err(read_error(err))),
+            }
+        }
+    }
+
+    fn consume(mut self: Pin<&mut Self>, amt: usize) {
+        self.buf.consume(amt);
+    }
+}
+
+/// A [`Stream`] of the chunks read from an [`InputStream`].
+///
+/// Created by [`InputStream::into_chunks`].
+#[derive(Debug)]
+pub struct Chunks {
+    stream: InputStream,
+}
+
+impl Stream for Chunks {
+    type Item = io::Result<Vec<u8>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let stream = &mut self.get_mut().stream;
+        match Pin::new(&mut *stream).poll_fill_buf(cx) {
+            Poll::Ready(Ok([])) => return Poll::Ready(None),
+            Poll::Ready(Ok(_)) => {}
+            Poll::Ready(Err(err)) => return Poll::Ready(Some(Err(err))),
+            Poll::Pending => return Poll::Pending,
+        }
+
+        // Hand out the buffer itself rather than copying it
+        Poll::Ready(Some(Ok(stream.buf.take())))
+    }
+}
+
+/// An async wrapper around a WASI output stream.
+///
+/// Implements [`AsyncWrite`], waking the task through the reactor once the
+/// stream is ready to accept more data, or has finished flushing.
+///
+/// Streams created with [`OutputStream::with_buffer`] collect small writes
+/// in a buffer, and only write them to the WASI stream once the buffer is
+/// full or the stream is flushed. Buffered bytes which haven't been flushed
+/// are lost when the stream is dropped.
+///
+/// Closing the stream through [`AsyncWrite::poll_close`] flushes it and then
+/// drops the WASI stream, which signals the end of the stream to the other
+/// side. Writes to a closed stream fail with [`io::ErrorKind::BrokenPipe`],
+/// while flushing or closing it again does nothing.
+#[derive(Debug)]
+pub struct OutputStream {
+    /// The WASI stream, until the stream is closed.
+    open: Option<Open>,
+    flushing: bool,
+    buf: Vec<u8>,
+    /// The size of the write buffer; writes aren't buffered if this is `0`.
+    capacity: usize,
+}
+
+/// A WASI output stream and the handle of its pollable.
+#[derive(Debug)]
+struct Open {
+    // IMPORTANT: the order of these fields here matters. `handle` holds a
+    // pollable which is a child of `inner`, so it must be dropped first.
+    handle: PollHandle,
+    inner: WasiOutputStream,
+}
+
+impl OutputStream {
+    /// Wrap a WASI output stream.
+    pub fn new(inner: WasiOutputStream, reactor: &Reactor) -> Self {
+        Self::with_buffer(inner, reactor, 0)
+    }
+
+    /// Wrap a WASI output stream, buffering up to `capacity` bytes of writes.
+    ///
+    /// Writes of at least `capacity` bytes bypass the buffer, once the bytes
+    /// buffered before them have been written. A `capacity` of `0` disables
+    /// buffering.
+    pub fn with_buffer(inner: WasiOutputStream, reactor: &Reactor, capacity: usize) -> Self {
+        let handle = reactor.register(inner.subscribe());
+        Self {
+            open: Some(Open { handle, inner }),
+            flushing: false,
+            buf: Vec::with_capacity(capacity),
+            capacity,
+        }
+    }
+}
+
+impl OutputStream {
+    /// Unwrap the WASI output stream, deregistering it from the reactor.
+    ///
+    /// Any bytes which were buffered but not yet written out are lost, so
+    /// flush the stream first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stream has been closed, since the WASI stream has been
+    /// dropped by then.
+    pub fn into_inner(self) -> WasiOutputStream {
+        // The handle's pollable is a child of the stream, so it has to be
+        // dropped before the stream can be handed out.
+        let Open { handle, inner } = self.open.expect("the stream has been closed");
+        drop(handle);
+        inner
+    }
+
+    /// Whether the stream has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.open.is_none()
+    }
+
+    /// Get the WASI stream, failing if the stream has been closed.
+    fn open(&self) -> io::Result<&Open> {
+        self.open
+            .as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::BrokenPipe, "the stream has been closed"))
+    }
+
+    /// Write bytes without waiting for the stream to become ready.
+    ///
+    /// Writes as many bytes as the stream currently permits, and fails with
+    /// [`io::ErrorKind::WouldBlock`] if it can't take any right now. On a
+    /// buffered stream, bytes are accepted as long as there is room in the
+    /// buffer.
+    pub fn try_write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.open()?;
+        if self.capacity > 0 {
+            if self.buf.len() + buf.len() > self.capacity {
+                self.try_drain()?;
+            }
+            if self.buf.is_empty() && buf.len() >= self.capacity {
+                return try_write(self.open()?, buf);
+            }
+            let len = buf.len().min(self.capacity - self.buf.len());
+            if len == 0 {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            self.buf.extend_from_slice(&buf[..len]);
+            return Ok(len);
+        }
+        try_write(self.open()?, buf)
+    }
+
+    /// Write all of `buf`, waiting for the stream whenever it can't take more.
+    ///
+    /// Each time the stream is ready, as much of `buf` is written as it
+    /// permits, checking for more room right away rather than waiting on the
+    /// reactor between writes. This takes fewer reactor turns than writing
+    /// through [`AsyncWrite`] for large buffers. Bytes buffered by
+    /// [`OutputStream::with_buffer`] are written out first, and `buf` itself
+    /// bypasses the buffer. The stream isn't flushed.
+    pub async fn write_all_buffered(&mut self, mut buf: &[u8]) -> io::Result<()> {
+        future::poll_fn(|cx| self.poll_drain(cx)).await?;
+        let open = self.open()?;
+        while !buf.is_empty() {
+            match open.inner.check_write().map_err(write_error)? {
+                0 => open.handle.ready().await,
+                permit => {
+                    let len = buf.len().min(usize::try_from(permit).unwrap_or(usize::MAX));
+                    open.inner.write(&buf[..len]).map_err(write_error)?;
+                    buf = &buf[len..];
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write all of `buf` and then flush the stream, once.
+    ///
+    /// See [`OutputStream::write_all_buffered`] for how `buf` is written.
+    /// Nothing is flushed until all of `buf` has been written, so a small
+    /// message goes out in a single flush.
+    pub async fn write_all_then_flush(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.write_all_buffered(buf).await?;
+        future::poll_fn(|cx| Pin::new(&mut *self).poll_flush(cx)).await
+    }
+
+    /// Write out as much of the buffer as the stream permits right now.
+    fn try_drain(&mut self) -> io::Result<()> {
+        while !self.buf.is_empty() {
+            match try_write(self.open()?, &self.buf) {
+                Ok(len) => drop(self.buf.drain(..len)),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Write out the whole buffer, waiting for the stream as needed.
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.buf.is_empty() {
+            let len = ready!(poll_write(self.open()?, cx, &self.buf))?;
+            self.buf.drain(..len);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for OutputStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        // There's nothing to write, so don't touch the stream
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let open = this.open()?;
+        if this.capacity == 0 {
+            return poll_write(open, cx, buf);
+        }
+
+        // Make room for the write first, so bytes are written out in order
+        if this.buf.len() + buf.len() > this.capacity {
+            ready!(this.poll_drain(cx))?;
+        }
+        if buf.len() >= this.capacity {
+            return poll_write(this.open()?, cx, buf);
+        }
+        this.buf.extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let total: usize = bufs.iter().map(|buf| buf.len()).sum();
+        if total == 0 {
+            return Poll::Ready(Ok(0));
+        }
+        this.open()?;
+        if this.capacity > 0 {
+            if this.buf.len() + total > this.capacity {
+                ready!(this.poll_drain(cx))?;
+            }
+            if total < this.capacity {
+                for buf in bufs {
+                    this.buf.extend_from_slice(buf);
+                }
+                return Poll::Ready(Ok(total));
+            }
+        }
+
+        // Join as many of the buffers as the stream permits into one write
+        let open = this.open()?;
+        loop {
+            match open.inner.check_write() {
+                Ok(0) => {
+                    if open.handle.poll(cx).is_pending() {
+                        return Poll::Pending;
+                    }
+                }
+                Ok(permit) => {
+                    let joined = gather(bufs, usize::try_from(permit).unwrap_or(usize::MAX));
+                    open.inner.write(&joined).map_err(write_error)?;
+                    return Poll::Ready(Ok(joined.len()));
+                }
+                Err(err) => return Poll::Ready(Err(write_error(err))),
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+
+        // Flushing only starts the flush; the stream becomes ready again once
+        // it has completed.
+        let Some(open) = &this.open else {
+            // A closed stream has nothing left to flush
+            return Poll::Ready(Ok(()));
+        };
+        if !this.flushing {
+            open.inner.flush().map_err(write_error)?;
+            this.flushing = true;
+        }
+        if open.handle.poll(cx).is_pending() {
+            return Poll::Pending;
+        }
+        this.flushing = false;
+
+        // Surface any error the flush ran into
+        open.inner.check_write().map_err(write_error)?;
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // Dropping the WASI stream is what closes it, once everything has
+        // been written out
+        ready!(self.as_mut().poll_flush(cx))?;
+        self.open = None;
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Read exactly enough bytes from `reader` to fill `buf`.
+///
+/// See [`InputStream::read_exact`].
+async fn read_exact(reader: &mut (impl AsyncRead + Unpin), buf: &mut [u8]) -> io::Result<()> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        let len =
+            future::poll_fn(|cx| Pin::new(&mut *reader).poll_read(cx, &mut buf[filled..])).await?;
+        if len == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the stream closed before the buffer was filled",
+            ));
+        }
+        filled += len;
+    }
+    Ok(())
+}
+
+/// Read exactly `N` bytes from `reader` into an array.
+async fn read_array<const N: usize>(reader: &mut (impl AsyncRead + Unpin)) -> io::Result<[u8; N]> {
+    let mut buf = [0; N];
+    read_exact(reader, &mut buf).await?;
+    Ok(buf)
+}
+
+/// Get the read size to use after a read of `len` bytes with `read_size`.
+///
+/// The read size doubles, up to `capacity`, after two consecutive reads
+/// which filled it up, and halves, down to [`MIN_READ_SIZE`], after a read
+/// which returned less than half of it.
+fn next_read_size(read_size: usize, len: usize, last_read_full: bool, capacity: usize) -> usize {
+    let full = len >= read_size;
+    if full && last_read_full {
+        (read_size * 2).min(capacity)
+    } else if len < read_size / 2 {
+        (read_size / 2).max(MIN_READ_SIZE.min(capacity))
+    } else {
+        read_size
+    }
+}
+
+/// Join the buffers into one, up to `limit` bytes.
+fn gather(bufs: &[IoSlice<'_>], limit: usize) -> Vec<u8> {
+    let total: usize = bufs.iter().map(|buf| buf.len()).sum();
+    let mut joined = Vec::with_capacity(total.min(limit));
+    for buf in bufs {
+        let len = buf.len().min(limit - joined.len());
+        joined.extend_from_slice(&buf[..len]);
+        if joined.len() == limit {
+            break;
+        }
+    }
+    joined
+}
+
+/// Write as many bytes as the stream currently permits, without waiting.
+fn try_write(open: &Open, buf: &[u8]) -> io::Result<usize> {
+    match open.inner.check_write().map_err(write_error)? {
+        0 => Err(io::ErrorKind::WouldBlock.into()),
+        permit => {
+            let len = buf.len().min(permit as usize);
+            open.inner.write(&buf[..len]).map_err(write_error)?;
+            Ok(len)
+        }
+    }
+}
+
+/// Write as many bytes as the stream permits, waiting until it permits any.
+fn poll_write(open: &Open, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+    loop {
+        match open.inner.check_write() {
+            // The stream can't take any data yet; wait for it to be ready
+            // and try again.
+            Ok(0) => {
+                if open.handle.poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+            }
+            Ok(permit) => {
+                let len = buf.len().min(permit as usize);
+                open.inner.write(&buf[..len]).map_err(write_error)?;
+                return Poll::Ready(Ok(len));
+            }
+            Err(err) => return Poll::Ready(Err(write_error(err))),
+        }
+    }
+}
+
+/// An [`InputStream`] reading from a file, which supports seeking.
+///
+/// Seeking re-opens the read stream on the file descriptor at the new offset,
+/// discarding anything which was buffered. Only regular files can seek;
+/// seeking in other kinds of descriptors fails with
+/// [`io::ErrorKind::Unsupported`].
+#[derive(Debug)]
+pub struct SeekableInputStream {
+    // IMPORTANT: the order of these fields here matters. `stream` is a child
+    // of `descriptor`, so it must be dropped first.
+    stream: InputStream,
+    descriptor: Descriptor,
+    reactor: Reactor,
+    /// The offset into the file of the next byte to be read.
+    pos: u64,
+}
+
+impl SeekableInputStream {
+    /// Open a stream reading from the start of a file.
+    pub fn new(descriptor: Descriptor, reactor: &Reactor) -> io::Result<Self> {
+        let stream = descriptor.read_via_stream(0).map_err(fs_error)?;
+        Ok(Self {
+            stream: InputStream::new(stream, reactor),
+            descriptor,
+            reactor: reactor.clone(),
+            pos: 0,
+        })
+    }
+}
+
+impl SeekableInputStream {
+    /// Read all bytes until the end of the file, appending them to `buf`.
+    ///
+    /// See [`InputStream::read_to_end`].
+    pub async fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        let len = self.stream.read_to_end(buf).await?;
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+impl AsyncRead for SeekableInputStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let res = Pin::new(&mut self.stream).poll_read(cx, buf);
+        if let Poll::Ready(Ok(len)) = res {
+            self.pos += len as u64;
+        }
+        res
+    }
+}
+
+impl AsyncBufRead for SeekableInputStream {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        Pin::new(&mut self.get_mut().stream).poll_fill_buf(cx)
+    }
+
+    fn consume(mut self: Pin<&mut Self>, amt: usize) {
+        // Only count what was actually buffered, the same way the stream
+        // clamps it.
+        self.pos += amt.min(self.stream.buffered_len()) as u64;
+        Pin::new(&mut self.stream).consume(amt);
+    }
+}
+
+impl AsyncSeek for SeekableInputStream {
+    fn poll_seek(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        pos: SeekFrom,
+    ) -> Poll<io::Result<u64>> {
+        let this = &mut *self;
+        let ty = this.descriptor.get_type().map_err(fs_error)?;
+        if ty != DescriptorType::RegularFile {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "only regular files support seeking",
+            )));
+        }
+
+        let offset = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(delta) => this.pos.checked_add_signed(delta),
+            SeekFrom::End(delta) => {
+                let stat = this.descriptor.stat().map_err(fs_error)?;
+                stat.size.checked_add_signed(delta)
+            }
+        };
+        let Some(offset) = offset else {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )));
+        };
+
+        // Replacing the stream drops the old one along with its buffer, so no
+        // stale bytes are returned after the seek.
+        let stream = this.descriptor.read_via_stream(offset).map_err(fs_error)?;
+        this.stream = InputStream::new(stream, &this.reactor);
+        this.pos = offset;
+        Poll::Ready(Ok(offset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gather_joins_buffers() {
+        let bufs = [
+            IoSlice::new(b"head"),
+            IoSlice::new(b""),
+            IoSlice::new(b"body"),
+        ];
+        assert_eq!(gather(&bufs, usize::MAX), b"headbody");
+    }
+
+    #[test]
+    fn gather_stops_at_limit() {
+        let bufs = [IoSlice::new(b"head"), IoSlice::new(b"body")];
+        assert_eq!(gather(&bufs, 6), b"headbo");
+        assert_eq!(gather(&bufs, 4), b"head");
+        assert_eq!(gather(&bufs, 2), b"he");
+    }
+
+    #[test]
+    fn read_size_grows_to_capacity() {
+        // A single full read isn't enough to grow
+        assert_eq!(next_read_size(512, 512, false, 2048), 512);
+        assert_eq!(next_read_size(512, 512, true, 2048), 1024);
+        assert_eq!(next_read_size(1024, 1024, true, 2048), 2048);
+        assert_eq!(next_read_size(2048, 2048, true, 2048), 2048);
+    }
+
+    #[test]
+    fn read_size_shrinks_to_floor() {
+        assert_eq!(next_read_size(2048, 1024, true, 2048), 2048);
+        assert_eq!(next_read_size(2048, 1023, true, 2048), 1024);
+        assert_eq!(next_read_size(1024, 10, false, 2048), MIN_READ_SIZE);
+        assert_eq!(next_read_size(MIN_READ_SIZE, 0, false, 2048), MIN_READ_SIZE);
+        // The floor never exceeds a small capacity
+        assert_eq!(next_read_size(100, 0, false, 100), 100);
+        assert_eq!(next_read_size(1, 1, true, 1), 1);
+    }
+
+    /// A reader returning a single byte per read, to exercise short reads.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl AsyncRead for Trickle<'_> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            let Some((&byte, rest)) = self.0.split_first() else {
+                return Poll::Ready(Ok(0));
+            };
+            buf[0] = byte;
+            self.0 = rest;
+            Poll::Ready(Ok(1))
+        }
+    }
+
+    #[test]
+    fn read_array_handles_short_reads() {
+        crate::block_on(|_| async {
+            let mut reader = Trickle(&[0x12, 0x34, 0x56, 0x78, 0x9a]);
+            let bytes: [u8; 2] = read_array(&mut reader).await.unwrap();
+            assert_eq!(u16::from_be_bytes(bytes), 0x1234);
+            assert_eq!(u16::from_le_bytes(bytes), 0x3412);
+            let bytes: [u8; 3] = read_array(&mut reader).await.unwrap();
+            assert_eq!(bytes, [0x56, 0x78, 0x9a]);
+        });
+    }
+
+    #[test]
+    fn read_array_fails_on_eof_mid_integer() {
+        crate::block_on(|_| async {
+            let mut reader = Trickle(&[1, 2, 3]);
+            let err = read_array::<4>(&mut reader).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+            let mut buf = [0; 0];
+            read_exact(&mut Trickle(&[]), &mut buf).await.unwrap();
+        });
+    }
+
+    #[test]
+    fn read_buf_tracks_consumed_bytes() {
+        let mut buf = ReadBuf::default();
+        buf.set(b"hello".to_vec());
+        let mut out = [0; 2];
+        assert_eq!(buf.read_into(&mut out), 2);
+        assert_eq!(&out, b"he");
+        assert_eq!(buf.available(), b"llo");
+        buf.consume(10);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn read_buf_peeks_past_consumed_bytes() {
+        let mut buf = ReadBuf::default();
+        buf.set(b"head".to_vec());
+        buf.consume(2);
+        assert_eq!(buf.first(4), b"ad");
+        buf.extend(b"er");
+        assert_eq!(buf.first(3), b"ade");
+        assert_eq!(buf.first(10), b"ader");
+        assert_eq!(buf.len(), 4);
+        assert_eq!(buf.take(), b"ader");
+        assert!(buf.is_empty());
+    }
+}
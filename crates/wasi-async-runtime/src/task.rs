@@ -0,0 +1,225 @@
+//! Spawned tasks, their join handles, and the wakers used to schedule them.
+
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use core::cell::Cell;
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Waker};
+#[cfg(feature = "std")]
+use futures_lite::FutureExt;
+#[cfg(feature = "std")]
+use std::panic::AssertUnwindSafe;
+
+/// A waker which marks its task as ready to be polled again.
+///
+/// The runtime is single-threaded, so all this needs to do is flip a flag
+/// which the executor loop checks before deciding whether to block on I/O.
+#[derive(Debug)]
+pub(crate) struct TaskWaker {
+    woken: AtomicBool,
+}
+
+impl TaskWaker {
+    /// Create a new waker. Tasks start out woken so they are polled at least
+    /// once.
+    pub(crate) fn new() -> Arc<Self> {
+        Arc::new(Self {
+            woken: AtomicBool::new(true),
+        })
+    }
+
+    /// Check whether the task was woken, resetting the flag.
+    pub(crate) fn take_woken(&self) -> bool {
+        self.woken.swap(false, Ordering::Relaxed)
+    }
+
+    /// Check whether the task was woken, without resetting the flag.
+    pub(crate) fn is_woken(&self) -> bool {
+        self.woken.load(Ordering::Relaxed)
+    }
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::Relaxed);
+    }
+}
+
+/// A spawned future, driven by the executor in `block_on`.
+pub(crate) struct Task {
+    fut: Pin<Box<dyn Future<Output = ()>>>,
+    waker: Arc<TaskWaker>,
+}
+
+impl Task {
+    /// Wrap a future in a task, returning the task and a handle to its
+    /// output.
+    pub(crate) fn new<F>(fut: F) -> (Self, JoinHandle<F::Output>)
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        let slot = Rc::new(Slot {
+            output: Cell::new(None),
+            waker: Cell::new(None),
+        });
+        let handle = JoinHandle { slot: slot.clone() };
+        let fut = async move {
+            // Catch panics inside of the task, so the future and the
+            // pollables it holds are dropped without taking down the event
+            // loop. The panic is resumed in whoever awaits the handle.
+            #[cfg(feature = "std")]
+            let output = FutureExt::catch_unwind(AssertUnwindSafe(fut)).await;
+            #[cfg(not(feature = "std"))]
+            let output = Ok(fut.await);
+            slot.output.set(Some(output));
+            if let Some(waker) = slot.waker.take() {
+                waker.wake();
+            }
+        };
+        let task = Self {
+            fut: Box::pin(fut),
+            waker: TaskWaker::new(),
+        };
+        (task, handle)
+    }
+
+    /// Whether the task has been woken since it was last polled.
+    pub(crate) fn is_woken(&self) -> bool {
+        self.waker.is_woken()
+    }
+
+    /// Poll the task if it was woken since it was last polled.
+    pub(crate) fn poll(&mut self) -> Poll<()> {
+        if !self.waker.take_woken() {
+            return Poll::Pending;
+        }
+        let waker = Waker::from(self.waker.clone());
+        let mut cx = Context::from_waker(&waker);
+        self.fut.as_mut().poll(&mut cx)
+    }
+}
+
+impl fmt::Debug for Task {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Task")
+            .field("waker", &self.waker)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The output of a task, or the payload of its panic.
+#[cfg(feature = "std")]
+type Outcome<T> = std::thread::Result<T>;
+#[cfg(not(feature = "std"))]
+type Outcome<T> = Result<T, core::convert::Infallible>;
+
+/// The slot a task writes its output into once it completes.
+struct Slot<T> {
+    output: Cell<Option<Outcome<T>>>,
+    waker: Cell<Option<Waker>>,
+}
+
+/// A handle to a spawned task, which resolves to the task's output.
+///
+/// Dropping the handle detaches the task: it keeps running, but its output
+/// is discarded.
+///
+/// # Panics
+///
+/// If the task panicked, awaiting the handle resumes the panic. The task
+/// itself is dropped as soon as it panics, releasing everything it had
+/// registered with the reactor, and the event loop carries on running the
+/// other tasks. The panic of a detached task is discarded.
+pub struct JoinHandle<T> {
+    slot: Rc<Slot<T>>,
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.slot.output.take() {
+            Some(Ok(output)) => Poll::Ready(output),
+            #[cfg(feature = "std")]
+            Some(Err(payload)) => std::panic::resume_unwind(payload),
+            None => {
+                self.slot.waker.set(Some(cx.waker().clone()));
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> fmt::Debug for JoinHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JoinHandle").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use core::cell::RefCell;
+    use futures_lite::future::yield_now;
+
+    use super::*;
+
+    #[test]
+    fn spawned_tasks_interleave() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let out = log.clone();
+        crate::block_on(|reactor| async move {
+            let handles: Vec<_> = ["a", "b"]
+                .into_iter()
+                .map(|name| {
+                    let log = log.clone();
+                    reactor.spawn(async move {
+                        for i in 0..2 {
+                            log.borrow_mut().push((name, i));
+                            yield_now().await;
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                handle.await;
+            }
+        });
+        assert_eq!(*out.borrow(), [("a", 0), ("b", 0), ("a", 1), ("b", 1)]);
+    }
+
+    #[test]
+    fn join_handle_returns_output() {
+        let output = crate::block_on(|reactor| async move { reactor.spawn(async { 42 }).await });
+        assert_eq!(output, 42);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn panicking_task_leaves_reactor_usable() {
+        let output = crate::block_on(|reactor| async move {
+            drop(reactor.spawn(async { panic!("the task failed") }));
+            reactor.spawn(async { 42 }).await
+        });
+        assert_eq!(output, 42);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    #[should_panic(expected = "the task failed")]
+    fn join_handle_resumes_panic() {
+        crate::block_on(|reactor| async move {
+            reactor.spawn(async { panic!("the task failed") }).await;
+        });
+    }
+}
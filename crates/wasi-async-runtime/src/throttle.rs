@@ -0,0 +1,181 @@
+//! Limiting the throughput of readers and writers.
+
+use super::monotonic::{self, Instant};
+use super::{Reactor, Timer};
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use core::time::Duration;
+use futures_lite::{AsyncRead, AsyncWrite};
+use std::io;
+
+/// Wraps a reader or writer, such as an [`InputStream`](crate::InputStream)
+/// or [`OutputStream`](crate::OutputStream), limiting its throughput to a
+/// number of bytes per second.
+///
+/// Reads and writes are limited separately, each by a token bucket holding
+/// up to a tenth of a second's worth of bytes. Once a bucket is empty the
+/// next read or write waits on a reactor timer until it has refilled, which
+/// keeps bursts small while honoring the rate over time.
+#[derive(Debug)]
+pub struct Throttle<T> {
+    inner: T,
+    reactor: Reactor,
+    read: Bucket,
+    write: Bucket,
+}
+
+impl<T> Throttle<T> {
+    /// Wrap a reader or writer, limiting it to `bytes_per_sec` bytes per
+    /// second in each direction.
+    pub fn new(inner: T, reactor: &Reactor, bytes_per_sec: u64) -> Self {
+        Self {
+            inner,
+            reactor: reactor.clone(),
+            read: Bucket::new(bytes_per_sec),
+            write: Bucket::new(bytes_per_sec),
+        }
+    }
+
+    /// Get a reference to the wrapped reader or writer.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the wrapped reader or writer.
+    ///
+    /// Reading or writing through it directly bypasses the limit.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Unwrap the reader or writer.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Throttle<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if buf.is_empty() {
+            return Pin::new(&mut this.inner).poll_read(cx, buf);
+        }
+        let Poll::Ready(max) = this.read.poll_acquire(cx, &this.reactor, buf.len()) else {
+            return Poll::Pending;
+        };
+        let res = Pin::new(&mut this.inner).poll_read(cx, &mut buf[..max]);
+        if let Poll::Ready(Ok(len)) = res {
+            this.read.take(len);
+        }
+        res
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Throttle<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if buf.is_empty() {
+            return Pin::new(&mut this.inner).poll_write(cx, buf);
+        }
+        let Poll::Ready(max) = this.write.poll_acquire(cx, &this.reactor, buf.len()) else {
+            return Poll::Pending;
+        };
+        let res = Pin::new(&mut this.inner).poll_write(cx, &buf[..max]);
+        if let Poll::Ready(Ok(len)) = res {
+            this.write.take(len);
+        }
+        res
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_close(cx)
+    }
+}
+
+/// A token bucket, where every token allows one byte through.
+#[derive(Debug)]
+struct Bucket {
+    rate: u64,
+    capacity: u64,
+    tokens: u64,
+    last_refill: Instant,
+    timer: Option<Timer>,
+}
+
+impl Bucket {
+    fn new(rate: u64) -> Self {
+        let rate = rate.max(1);
+        let capacity = (rate / 10).max(1);
+        Self {
+            rate,
+            capacity,
+            tokens: capacity,
+            last_refill: monotonic::now(),
+            timer: None,
+        }
+    }
+
+    /// Add the tokens which accumulated since the last refill.
+    fn refill(&mut self) {
+        let now = monotonic::now();
+        let elapsed = now.duration_since(self.last_refill).as_nanos();
+        let added = elapsed * self.rate as u128 / 1_000_000_000;
+        // Only move the refill time forward once whole tokens were added, so
+        // slow rates still accumulate tokens across many short polls.
+        if added > 0 {
+            let added = u64::try_from(added).unwrap_or(u64::MAX);
+            self.tokens = self.tokens.saturating_add(added).min(self.capacity);
+            self.last_refill = now;
+        }
+    }
+
+    /// Wait until tokens are available, returning how many bytes may be
+    /// transferred, up to `want`.
+    fn poll_acquire(
+        &mut self,
+        cx: &mut Context<'_>,
+        reactor: &Reactor,
+        want: usize,
+    ) -> Poll<usize> {
+        loop {
+            self.refill();
+            if self.tokens > 0 {
+                self.timer = None;
+                let max = usize::try_from(self.tokens).unwrap_or(usize::MAX);
+                return Poll::Ready(max.min(want));
+            }
+
+            // Wait long enough for the bucket to hold as much as we want, as
+            // far as it can.
+            let timer = self.timer.get_or_insert_with(|| {
+                let needed = (want as u64).min(self.capacity) as u128;
+                let nanos = needed * 1_000_000_000 / self.rate as u128;
+                let nanos = u64::try_from(nanos).unwrap_or(u64::MAX);
+                Timer::after(reactor, Duration::from_nanos(nanos))
+            });
+            if Pin::new(timer).poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            self.timer = None;
+        }
+    }
+
+    /// Remove the tokens for `len` bytes which were transferred.
+    fn take(&mut self, len: usize) {
+        self.tokens = self.tokens.saturating_sub(len as u64);
+    }
+}
@@ -0,0 +1,237 @@
+//! Timers backed by the WASI monotonic clock.
+
+use super::monotonic::{self, Instant};
+use super::reactor::PollHandle;
+use super::Reactor;
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use core::time::Duration;
+use wasi::clocks::monotonic_clock;
+
+/// A future which resolves once a point in time has been reached.
+///
+/// Dropping the timer before it fires deregisters it from the reactor.
+#[derive(Debug)]
+pub struct Timer {
+    handle: PollHandle,
+}
+
+impl Timer {
+    /// Create a timer which fires once `dur` has elapsed.
+    pub fn after(reactor: &Reactor, dur: Duration) -> Self {
+        let pollable = monotonic_clock::subscribe_duration(duration_to_nanos(dur));
+        Self {
+            handle: reactor.register(pollable),
+        }
+    }
+
+    /// Create a timer which fires once the monotonic clock reaches `instant`.
+    pub fn at(reactor: &Reactor, instant: Instant) -> Self {
+        let pollable = monotonic_clock::subscribe_instant(instant.as_nanos());
+        Self {
+            handle: reactor.register(pollable),
+        }
+    }
+}
+
+impl Future for Timer {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.handle.poll(cx)
+    }
+}
+
+/// A point in time by which some work has to be done.
+///
+/// Unlike a timeout, a deadline doesn't restart for every operation: sharing
+/// one deadline across several operations bounds the time they take
+/// together, with each of them getting whatever time is left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Deadline {
+    instant: Instant,
+}
+
+impl Deadline {
+    /// Create a deadline at `instant`.
+    pub fn at(instant: Instant) -> Self {
+        Self { instant }
+    }
+
+    /// Create a deadline once `dur` has elapsed from now, saturating on
+    /// overflow.
+    pub fn after(dur: Duration) -> Self {
+        let now = monotonic::now();
+        let instant = now
+            .checked_add(dur)
+            .unwrap_or_else(|| now + Duration::from_nanos(u64::MAX - now.as_nanos()));
+        Self { instant }
+    }
+
+    /// Get the point in time of the deadline.
+    pub fn instant(&self) -> Instant {
+        self.instant
+    }
+
+    /// Get the time left until the deadline, or zero if it has passed.
+    pub fn remaining(&self) -> Duration {
+        self.instant.duration_since(monotonic::now())
+    }
+
+    /// Whether the deadline has passed.
+    pub fn has_elapsed(&self) -> bool {
+        monotonic::now() >= self.instant
+    }
+
+    /// Create a timer which fires at the deadline.
+    pub fn timer(&self, reactor: &Reactor) -> Timer {
+        Timer::at(reactor, self.instant)
+    }
+}
+
+impl From<Instant> for Deadline {
+    fn from(instant: Instant) -> Self {
+        Self::at(instant)
+    }
+}
+
+/// A timer which fires repeatedly, once every period.
+///
+/// Each deadline is computed from the previous one rather than from when the
+/// last tick was observed, so ticks don't drift over time. What happens when
+/// ticks are consumed slower than they fire is controlled by the
+/// [`MissedTickBehavior`].
+#[derive(Debug)]
+pub struct Interval {
+    reactor: Reactor,
+    period: Duration,
+    next: Instant,
+    behavior: MissedTickBehavior,
+}
+
+/// What an [`Interval`] does about ticks which were missed, because the
+/// previous tick was consumed late.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MissedTickBehavior {
+    /// Fire the missed ticks right away, one after the other, until the
+    /// interval has caught up.
+    #[default]
+    Burst,
+    /// Fire the next tick one period after the late tick was consumed,
+    /// shifting all later deadlines.
+    Delay,
+    /// Drop the missed ticks, and fire on the next deadline of the original
+    /// schedule.
+    Skip,
+}
+
+impl Interval {
+    /// Create an interval whose first tick fires once `period` has elapsed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is zero.
+    pub fn new(reactor: &Reactor, period: Duration) -> Self {
+        assert!(!period.is_zero(), "the period of an interval can't be zero");
+        Self {
+            reactor: reactor.clone(),
+            period,
+            next: monotonic::now() + period,
+            behavior: MissedTickBehavior::default(),
+        }
+    }
+
+    /// Set what happens to ticks which were missed.
+    pub fn set_missed_tick_behavior(&mut self, behavior: MissedTickBehavior) {
+        self.behavior = behavior;
+    }
+
+    /// Get what happens to ticks which were missed.
+    pub fn missed_tick_behavior(&self) -> MissedTickBehavior {
+        self.behavior
+    }
+
+    /// Get the period of the interval.
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Restart the interval, so the next tick fires one period from now.
+    pub fn reset(&mut self) {
+        self.next = monotonic::now() + self.period;
+    }
+
+    /// Wait for the next tick, returning the deadline it was scheduled for.
+    ///
+    /// Dropping the future before it resolves doesn't consume the tick.
+    pub async fn tick(&mut self) -> Instant {
+        let deadline = self.next;
+        Timer::at(&self.reactor, deadline).await;
+
+        self.next = self
+            .behavior
+            .next_deadline(deadline, monotonic::now(), self.period);
+        deadline
+    }
+}
+
+impl MissedTickBehavior {
+    /// Get the deadline of the tick after the one scheduled for `deadline`,
+    /// which was consumed at `now`.
+    fn next_deadline(self, deadline: Instant, now: Instant, period: Duration) -> Instant {
+        match self {
+            Self::Burst => deadline + period,
+            Self::Delay => now + period,
+            Self::Skip => {
+                // Move on to the first deadline of the schedule after now
+                let periods = now.duration_since(deadline).as_nanos() / period.as_nanos() + 1;
+                let offset = period.as_nanos().saturating_mul(periods);
+                deadline + Duration::from_nanos(u64::try_from(offset).unwrap_or(u64::MAX))
+            }
+        }
+    }
+}
+
+/// Convert a `Duration` to the nanoseconds the monotonic clock works in,
+/// saturating on overflow.
+fn duration_to_nanos(dur: Duration) -> u64 {
+    u64::try_from(dur.as_nanos()).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERIOD: Duration = Duration::from_nanos(10);
+
+    /// The next deadline after a tick scheduled at 100ns consumed at `now`.
+    fn next(behavior: MissedTickBehavior, now: u64) -> u64 {
+        let deadline = Instant::from_nanos(100);
+        behavior
+            .next_deadline(deadline, Instant::from_nanos(now), PERIOD)
+            .as_nanos()
+    }
+
+    #[test]
+    fn burst_keeps_the_schedule() {
+        assert_eq!(next(MissedTickBehavior::Burst, 100), 110);
+        assert_eq!(next(MissedTickBehavior::Burst, 135), 110);
+    }
+
+    #[test]
+    fn delay_shifts_the_schedule() {
+        assert_eq!(next(MissedTickBehavior::Delay, 100), 110);
+        assert_eq!(next(MissedTickBehavior::Delay, 135), 145);
+    }
+
+    #[test]
+    fn skip_moves_to_the_next_deadline_after_now() {
+        assert_eq!(next(MissedTickBehavior::Skip, 100), 110);
+        assert_eq!(next(MissedTickBehavior::Skip, 105), 110);
+        assert_eq!(next(MissedTickBehavior::Skip, 135), 140);
+        // A deadline which is exactly now has already been missed
+        assert_eq!(next(MissedTickBehavior::Skip, 120), 130);
+    }
+}
@@ -0,0 +1,151 @@
+//! Waiting for a group of tasks to finish.
+
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use core::future;
+use core::task::{Poll, Waker};
+
+/// Counts outstanding work, and wakes the tasks waiting on it once all of it
+/// is done.
+///
+/// Work is counted either with [`WaitGroup::add`] and [`WaitGroup::done`],
+/// or by handing out [`Worker`]s which count as done once dropped. Clones of
+/// a wait group share their count.
+#[derive(Debug, Clone, Default)]
+pub struct WaitGroup {
+    inner: Rc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    count: Cell<usize>,
+    wakers: RefCell<Vec<Waker>>,
+}
+
+impl WaitGroup {
+    /// Create a new wait group without any outstanding work.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `n` units of outstanding work.
+    pub fn add(&self, n: usize) {
+        self.inner.count.set(self.inner.count.get() + n);
+    }
+
+    /// Mark one unit of work as done, waking the waiting tasks if it was the
+    /// last.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no outstanding work.
+    pub fn done(&self) {
+        let count = self.inner.count.get();
+        assert!(
+            count > 0,
+            "`WaitGroup::done` called without outstanding work"
+        );
+        self.inner.count.set(count - 1);
+        if count == 1 {
+            let wakers = core::mem::take(&mut *self.inner.wakers.borrow_mut());
+            for waker in wakers {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Add one unit of outstanding work, which is done once the returned
+    /// worker is dropped.
+    pub fn worker(&self) -> Worker {
+        self.add(1);
+        Worker {
+            group: self.clone(),
+        }
+    }
+
+    /// Get the number of units of outstanding work.
+    pub fn count(&self) -> usize {
+        self.inner.count.get()
+    }
+
+    /// Wait until there is no outstanding work left.
+    pub async fn wait(&self) {
+        future::poll_fn(|cx| {
+            if self.count() == 0 {
+                return Poll::Ready(());
+            }
+            let mut wakers = self.inner.wakers.borrow_mut();
+            if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                wakers.push(cx.waker().clone());
+            }
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+/// A unit of work counted by a [`WaitGroup`], which is done once dropped.
+///
+/// Cloning a worker adds another unit of work.
+#[derive(Debug)]
+pub struct Worker {
+    group: WaitGroup,
+}
+
+impl Clone for Worker {
+    fn clone(&self) -> Self {
+        self.group.worker()
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.group.done();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_lite::future::yield_now;
+
+    use super::*;
+
+    #[test]
+    fn waits_for_all_workers() {
+        let finished = Rc::new(Cell::new(0));
+        crate::block_on(|reactor| async move {
+            let group = WaitGroup::new();
+            for i in 0..10 {
+                let worker = group.worker();
+                let finished = finished.clone();
+                drop(reactor.spawn(async move {
+                    for _ in 0..i {
+                        yield_now().await;
+                    }
+                    finished.set(finished.get() + 1);
+                    drop(worker);
+                }));
+            }
+            group.wait().await;
+            assert_eq!(group.count(), 0);
+            assert_eq!(finished.get(), 10);
+        });
+    }
+
+    #[test]
+    fn add_and_done() {
+        let group = WaitGroup::new();
+        group.add(2);
+        group.done();
+        assert_eq!(group.count(), 1);
+        group.done();
+        assert_eq!(group.count(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "without outstanding work")]
+    fn done_without_work_panics() {
+        WaitGroup::new().done();
+    }
+}
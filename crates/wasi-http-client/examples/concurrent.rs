@@ -1,3 +1,4 @@
+use async_iterator::Iterator;
 use futures_concurrency::prelude::*;
 use wasi_async_runtime::block_on;
 use wasi_http_client::{Client, Method, Request, Response, Result};
@@ -22,7 +23,6 @@ fn main() -> Result<()> {
             let req = Request::new(Method::Get, url);
             let res = client.send(req).await?;
 
-            let body = res.collect().await;
             let body = read_to_end(res).await;
             let body = String::from_utf8(body).unwrap();
             println!("{body}");
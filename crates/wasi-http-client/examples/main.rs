@@ -1,3 +1,4 @@
+use async_iterator::Iterator;
 use wasi_async_runtime::block_on;
 use wasi_http_client::{Client, Method, Request, Response, Result};
 
@@ -18,7 +19,7 @@ fn main() -> Result<()> {
 
 async fn read_to_end(mut res: Response) -> Vec<u8> {
     let mut body = vec![];
-    while let Some(chunk) = res.next_chunk().await {
+    while let Some(chunk) = res.next().await {
         let chunk = chunk.unwrap();
         body.extend_from_slice(&chunk);
     }
@@ -0,0 +1,362 @@
+use futures_lite::{ready, AsyncRead, AsyncReadExt, AsyncWriteExt, Stream};
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use wasi::http::types::{http_error_code, OutgoingBody};
+use wasi_async_runtime::{OutputStream, Reactor, StreamError};
+
+use crate::Error;
+
+/// Read 2kb chunks at a time from a streaming body
+const CHUNK_SIZE: usize = 2048;
+
+/// The body of an HTTP request
+pub struct Body {
+    kind: BodyKind,
+}
+
+enum BodyKind {
+    Empty,
+    Bytes(Vec<u8>),
+    Reader {
+        reader: Pin<Box<dyn AsyncRead>>,
+        len: Option<u64>,
+    },
+}
+
+impl Body {
+    /// Create an empty body.
+    pub fn empty() -> Self {
+        Self {
+            kind: BodyKind::Empty,
+        }
+    }
+
+    /// Create a body from a buffer of bytes.
+    pub fn from_bytes(bytes: impl Into<Vec<u8>>) -> Self {
+        Self {
+            kind: BodyKind::Bytes(bytes.into()),
+        }
+    }
+
+    /// Create a body which streams its contents from `reader`.
+    ///
+    /// If `len` is known it is sent as the `Content-Length` of the request,
+    /// and sending fails if the reader yields a different number of bytes.
+    /// Otherwise the host frames the body using chunked transfer encoding.
+    pub fn from_reader(reader: impl AsyncRead + 'static, len: Option<u64>) -> Self {
+        Self {
+            kind: BodyKind::Reader {
+                reader: Box::pin(reader),
+                len,
+            },
+        }
+    }
+
+    /// Create a body which streams its contents from a stream of chunks.
+    ///
+    /// The next chunk is only pulled from the stream once the outgoing body
+    /// can take more bytes. An error from the stream aborts sending the
+    /// request, and is returned as [`Error::Body`]. The length of the body
+    /// isn't known up front, so the host frames it using chunked transfer
+    /// encoding.
+    pub fn from_stream(stream: impl Stream<Item = io::Result<Vec<u8>>> + 'static) -> Self {
+        let reader = StreamReader {
+            stream: Box::pin(stream),
+            chunk: Vec::new(),
+            pos: 0,
+        };
+        Self::from_reader(reader, None)
+    }
+
+    /// The length of the body, if known.
+    pub fn len(&self) -> Option<u64> {
+        match &self.kind {
+            BodyKind::Empty => Some(0),
+            BodyKind::Bytes(bytes) => Some(bytes.len() as u64),
+            BodyKind::Reader { len, .. } => *len,
+        }
+    }
+
+    /// Copy the body, if it can be sent more than once.
+    ///
+    /// Bodies streamed from a reader can only be sent once.
+    pub(crate) fn try_clone(&self) -> Option<Self> {
+        let kind = match &self.kind {
+            BodyKind::Empty => BodyKind::Empty,
+            BodyKind::Bytes(bytes) => BodyKind::Bytes(bytes.clone()),
+            BodyKind::Reader { .. } => return None,
+        };
+        Some(Self { kind })
+    }
+
+    /// Returns `true` if the body is known to be empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == Some(0)
+    }
+
+    /// Write the body into the outgoing body of a request, and finish it.
+    ///
+    /// Fails if the body doesn't have the length declared by the request's
+    /// `Content-Length` header, or else the length it was created with.
+    pub(crate) async fn send(
+        self,
+        outgoing: OutgoingBody,
+        content_length: Option<u64>,
+        reactor: &Reactor,
+    ) -> crate::Result<()> {
+        // `stream` is a child of `outgoing` which means we cannot finish the
+        // parent before we drop the child
+        let stream = outgoing
+            .write()
+            .expect("cannot call `write` twice on an outgoing body");
+        let mut stream = OutputStream::new(stream, reactor);
+        let len = content_length.or(self.len());
+        match self.kind {
+            BodyKind::Empty => {
+                if let Some(err) = check_length(0, len, true) {
+                    return Err(err);
+                }
+            }
+            BodyKind::Bytes(bytes) => {
+                if let Some(err) = check_length(bytes.len() as u64, len, true) {
+                    return Err(err);
+                }
+                // Wait for all bytes to be flushed before the body can be
+                // finished
+                stream
+                    .write_all_then_flush(&bytes)
+                    .await
+                    .map_err(write_error)?;
+            }
+            BodyKind::Reader { reader, .. } => copy_reader(reader, len, &mut stream).await?,
+        }
+        drop(stream);
+        OutgoingBody::finish(outgoing, None)?;
+        Ok(())
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::from_bytes(bytes)
+    }
+}
+
+impl From<String> for Body {
+    fn from(string: String) -> Self {
+        Self::from_bytes(string)
+    }
+}
+
+impl From<&str> for Body {
+    fn from(string: &str) -> Self {
+        Self::from_bytes(string)
+    }
+}
+
+impl Default for Body {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl fmt::Debug for Body {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Body").field("len", &self.len()).finish()
+    }
+}
+
+/// Reads the chunks of a stream as a single sequence of bytes.
+struct StreamReader {
+    stream: Pin<Box<dyn Stream<Item = io::Result<Vec<u8>>>>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl AsyncRead for StreamReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        // Skip over empty chunks, which would otherwise read as the end
+        while this.pos == this.chunk.len() {
+            match ready!(this.stream.as_mut().poll_next(cx)) {
+                Some(chunk) => {
+                    this.chunk = chunk?;
+                    this.pos = 0;
+                }
+                None => return Poll::Ready(Ok(0)),
+            }
+        }
+        let available = &this.chunk[this.pos..];
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        this.pos += len;
+        Poll::Ready(Ok(len))
+    }
+}
+
+/// Copy the bytes from `reader` into `stream`, checking them against the
+/// declared length, and flush the stream.
+async fn copy_reader(
+    mut reader: Pin<Box<dyn AsyncRead>>,
+    len: Option<u64>,
+    stream: &mut OutputStream,
+) -> crate::Result<()> {
+    let mut buf = vec![0; CHUNK_SIZE];
+    let mut written = 0;
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        written += n as u64;
+        if let Some(err) = check_length(written, len, false) {
+            return Err(err);
+        }
+        stream
+            .write_all_buffered(&buf[..n])
+            .await
+            .map_err(write_error)?;
+    }
+
+    if let Some(err) = check_length(written, len, true) {
+        return Err(err);
+    }
+
+    // Wait for all bytes to be flushed before the body can be finished
+    stream.flush().await.map_err(write_error)
+}
+
+/// Check the number of bytes `written` so far against the length `len` the
+/// body was declared with, if any.
+///
+/// Until the body has `ended`, it only fails once it is already too long.
+fn check_length(written: u64, len: Option<u64>, ended: bool) -> Option<Error> {
+    let len = len?;
+    if written > len || (ended && written < len) {
+        return Some(length_mismatch(written, len));
+    }
+    None
+}
+
+/// The error for a body which doesn't match the length it was declared with.
+///
+/// `written` is the number of bytes the body had produced when the mismatch
+/// was noticed.
+fn length_mismatch(written: u64, len: u64) -> Error {
+    let msg = if written > len {
+        format!("body longer than Content-Length: got more than {len} bytes")
+    } else {
+        format!("body shorter than Content-Length: got {written} of {len} bytes")
+    };
+    Error::Body(io::Error::new(io::ErrorKind::InvalidInput, msg))
+}
+
+/// Convert an error writing to the outgoing body stream into an HTTP error.
+///
+/// If the host reports why the stream failed as an HTTP error code, that
+/// code is returned instead.
+fn write_error(err: io::Error) -> Error {
+    let code = err
+        .get_ref()
+        .and_then(|err| err.downcast_ref::<StreamError>())
+        .and_then(|err| http_error_code(err.get_ref()));
+    match code {
+        Some(code) => code.into(),
+        None => Error::Body(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_lite::stream;
+
+    use super::*;
+
+    /// Read a streaming body to the end.
+    fn read_body(body: Body) -> io::Result<Vec<u8>> {
+        let BodyKind::Reader { mut reader, .. } = body.kind else {
+            panic!("expected a streaming body");
+        };
+        wasi_async_runtime::block_on(|_| async move {
+            let mut out = Vec::new();
+            reader.read_to_end(&mut out).await?;
+            Ok(out)
+        })
+    }
+
+    #[test]
+    fn stream_chunks_are_concatenated() {
+        let chunks = [
+            b"hello".to_vec(),
+            Vec::new(),
+            b", ".to_vec(),
+            b"world".to_vec(),
+        ];
+        let body = Body::from_stream(stream::iter(chunks.map(Ok)));
+        assert_eq!(body.len(), None);
+        assert!(body.try_clone().is_none());
+        assert_eq!(read_body(body).unwrap(), b"hello, world");
+    }
+
+    #[test]
+    fn stream_errors_are_returned() {
+        let chunks = [
+            Ok(b"partial".to_vec()),
+            Err(io::Error::other("the stream failed")),
+        ];
+        let body = Body::from_stream(stream::iter(chunks));
+        let err = read_body(body).unwrap_err();
+        assert_eq!(err.to_string(), "the stream failed");
+    }
+
+    #[test]
+    fn buffered_bodies_know_their_length() {
+        assert_eq!(Body::empty().len(), Some(0));
+        assert!(Body::empty().is_empty());
+        let body = Body::from_bytes("hello");
+        assert_eq!(body.len(), Some(5));
+        assert_eq!(body.try_clone().unwrap().len(), Some(5));
+    }
+
+    /// Unwrap the `io::Error` from a length mismatch.
+    fn mismatch(err: Option<Error>) -> io::Error {
+        match err {
+            Some(Error::Body(err)) => err,
+            other => panic!("expected a body error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn matching_length_is_accepted() {
+        assert!(check_length(5, Some(5), true).is_none());
+        assert!(check_length(3, Some(5), false).is_none());
+        assert!(check_length(3, None, true).is_none());
+    }
+
+    #[test]
+    fn short_body_is_rejected_once_ended() {
+        let err = mismatch(check_length(3, Some(5), true));
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(
+            err.to_string(),
+            "body shorter than Content-Length: got 3 of 5 bytes"
+        );
+    }
+
+    #[test]
+    fn long_body_is_rejected_right_away() {
+        let err = mismatch(check_length(6, Some(5), false));
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(
+            err.to_string(),
+            "body longer than Content-Length: got more than 5 bytes"
+        );
+        assert!(check_length(6, Some(5), true).is_some());
+    }
+}
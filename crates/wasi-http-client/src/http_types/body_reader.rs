@@ -0,0 +1,168 @@
+use async_compression::futures::bufread::{GzipDecoder, ZlibDecoder};
+use futures_lite::io::{BufReader, Cursor};
+use futures_lite::{AsyncBufRead, AsyncBufReadExt, AsyncRead};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use wasi::http::types::IncomingBody;
+use wasi_async_runtime::{InputStream, Reactor};
+
+use crate::Trailers;
+
+/// A content coding a response body can be decoded from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ContentEncoding {
+    Identity,
+    Gzip,
+    Deflate,
+}
+
+impl ContentEncoding {
+    /// Parse the value of a `Content-Encoding` header.
+    ///
+    /// Returns `None` for codings we can't decode, including lists of more
+    /// than one coding.
+    pub(crate) fn parse(value: &[u8]) -> Option<Self> {
+        let value = std::str::from_utf8(value).ok()?.trim();
+        if value.eq_ignore_ascii_case("identity") {
+            Some(Self::Identity)
+        } else if value.eq_ignore_ascii_case("gzip") || value.eq_ignore_ascii_case("x-gzip") {
+            Some(Self::Gzip)
+        } else if value.eq_ignore_ascii_case("deflate") {
+            Some(Self::Deflate)
+        } else {
+            None
+        }
+    }
+}
+
+/// A reader over the body of an HTTP response.
+///
+/// See [`Response::into_body_reader`](crate::Response::into_body_reader).
+#[derive(Debug)]
+pub struct BodyReader {
+    // IMPORTANT: the order of these fields here matters. `stream` is a child
+    // of `incoming_body`, so it must be dropped first.
+    stream: Decoder,
+    /// The incoming body the stream belongs to, unless the body was created
+    /// from bytes.
+    incoming_body: Option<(IncomingBody, Reactor)>,
+}
+
+/// The body stream, decoded according to its content coding.
+#[derive(Debug)]
+enum Decoder {
+    Bytes(Cursor<Vec<u8>>),
+    Identity(InputStream),
+    Gzip(BufReader<GzipDecoder<InputStream>>),
+    Deflate(BufReader<ZlibDecoder<InputStream>>),
+}
+
+impl BodyReader {
+    pub(crate) fn new(
+        stream: InputStream,
+        incoming_body: IncomingBody,
+        encoding: ContentEncoding,
+        reactor: Reactor,
+    ) -> Self {
+        let stream = match encoding {
+            ContentEncoding::Identity => Decoder::Identity(stream),
+            ContentEncoding::Gzip => Decoder::Gzip(BufReader::new(GzipDecoder::new(stream))),
+            // HTTP's "deflate" coding is the zlib format, not raw deflate
+            ContentEncoding::Deflate => Decoder::Deflate(BufReader::new(ZlibDecoder::new(stream))),
+        };
+        Self {
+            stream,
+            incoming_body: Some((incoming_body, reactor)),
+        }
+    }
+
+    /// Create a reader over a body held in memory, which has no trailers.
+    pub(crate) fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self {
+            stream: Decoder::Bytes(Cursor::new(bytes)),
+            incoming_body: None,
+        }
+    }
+
+    /// The WASI stream the body is read from, if the body isn't decoded or
+    /// held in memory.
+    pub(crate) fn identity_stream(&mut self) -> Option<&mut InputStream> {
+        match &mut self.stream {
+            Decoder::Identity(stream) => Some(stream),
+            _ => None,
+        }
+    }
+
+    /// Wait for the trailers of the response.
+    ///
+    /// Trailers follow the body, so this can only be called once the body has
+    /// been read to the end; calling it earlier returns an error. Returns
+    /// `None` if the response had no trailers.
+    pub async fn trailers(mut self) -> crate::Result<Option<Trailers>> {
+        match self.fill_buf().await {
+            Ok([]) => {}
+            Ok(_) => {
+                return Err(crate::Error::Body(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "trailers were requested before the body was read to the end",
+                )))
+            }
+            Err(err) => return Err(err.into()),
+        }
+
+        // The body stream is a child of the incoming body, so it has to be
+        // dropped before the incoming body can be finished.
+        let Self {
+            stream,
+            incoming_body,
+        } = self;
+        drop(stream);
+        let Some((incoming_body, reactor)) = incoming_body else {
+            return Ok(None);
+        };
+        let trailers = IncomingBody::finish(incoming_body);
+        reactor.wait_for(trailers.subscribe()).await;
+
+        // NOTE: the first `unwrap` is to ensure readiness, the second `unwrap`
+        // is to trap if we try and get the trailers more than once. The final
+        // `?` is go raise the actual error if there is one.
+        let trailers = trailers.get().unwrap().unwrap()?;
+        Ok(trailers.map(Trailers::from))
+    }
+}
+
+impl AsyncRead for BodyReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        match &mut self.stream {
+            Decoder::Bytes(stream) => Pin::new(stream).poll_read(cx, buf),
+            Decoder::Identity(stream) => Pin::new(stream).poll_read(cx, buf),
+            Decoder::Gzip(stream) => Pin::new(stream).poll_read(cx, buf),
+            Decoder::Deflate(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncBufRead for BodyReader {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        match &mut self.get_mut().stream {
+            Decoder::Bytes(stream) => Pin::new(stream).poll_fill_buf(cx),
+            Decoder::Identity(stream) => Pin::new(stream).poll_fill_buf(cx),
+            Decoder::Gzip(stream) => Pin::new(stream).poll_fill_buf(cx),
+            Decoder::Deflate(stream) => Pin::new(stream).poll_fill_buf(cx),
+        }
+    }
+
+    fn consume(mut self: Pin<&mut Self>, amt: usize) {
+        match &mut self.stream {
+            Decoder::Bytes(stream) => Pin::new(stream).consume(amt),
+            Decoder::Identity(stream) => Pin::new(stream).consume(amt),
+            Decoder::Gzip(stream) => Pin::new(stream).consume(amt),
+            Decoder::Deflate(stream) => Pin::new(stream).consume(amt),
+        }
+    }
+}
@@ -0,0 +1,413 @@
+use futures_lite::{ready, AsyncBufRead, AsyncRead, AsyncWrite};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::Trailers;
+
+/// The longest chunk size or trailer line we accept.
+const MAX_LINE_LEN: usize = 8 * 1024;
+
+/// The most bytes sent in a single chunk.
+const MAX_CHUNK_LEN: usize = 64 * 1024;
+
+/// A reader decoding a body sent with `Transfer-Encoding: chunked`.
+///
+/// `wasi:http` decodes chunked bodies itself, so this is only needed when
+/// speaking HTTP/1.1 directly over a socket, such as a
+/// [`TcpStream`](wasi_async_runtime::TcpStream). Reading yields the body
+/// without its framing, and ends after the terminating zero-length chunk.
+/// Chunk extensions are ignored. Malformed framing, or a stream which ends
+/// in the middle of the body, fails with an [`io::Error`].
+#[derive(Debug)]
+pub struct ChunkedDecoder<R> {
+    reader: R,
+    state: DecodeState,
+    line: Vec<u8>,
+    trailers: Trailers,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DecodeState {
+    /// Reading the line holding the size of the next chunk.
+    Size,
+    /// Reading the data of a chunk, with this many bytes left.
+    Data(u64),
+    /// Reading the line break following the data of a chunk.
+    DataEnd,
+    /// Reading the trailers following the last chunk.
+    Trailers,
+    /// The whole body has been read.
+    Done,
+}
+
+impl<R: AsyncBufRead + Unpin> ChunkedDecoder<R> {
+    /// Decode the chunked body read from `reader`.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            state: DecodeState::Size,
+            line: Vec::new(),
+            trailers: Trailers::new(),
+        }
+    }
+
+    /// Get the trailers which followed the body.
+    ///
+    /// Returns `None` until the body has been read to the end.
+    pub fn trailers(&self) -> Option<&Trailers> {
+        (self.state == DecodeState::Done).then_some(&self.trailers)
+    }
+
+    /// Unwrap the reader.
+    ///
+    /// Once the body has been read to the end, the reader is positioned
+    /// right after it.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Read a line, without its line break.
+    fn poll_line(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<Vec<u8>>> {
+        loop {
+            let available = ready!(Pin::new(&mut self.reader).poll_fill_buf(cx))?;
+            if available.is_empty() {
+                return Poll::Ready(Err(truncated()));
+            }
+            let (len, done) = match available.iter().position(|&b| b == b'\n') {
+                Some(i) => (i + 1, true),
+                None => (available.len(), false),
+            };
+            self.line.extend_from_slice(&available[..len]);
+            Pin::new(&mut self.reader).consume(len);
+            if self.line.len() > MAX_LINE_LEN {
+                return Poll::Ready(Err(invalid("line in chunked body is too long")));
+            }
+            if done {
+                let mut line = std::mem::take(&mut self.line);
+                line.pop();
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+                return Poll::Ready(Ok(line));
+            }
+        }
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncRead for ChunkedDecoder<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        loop {
+            match this.state {
+                DecodeState::Size => {
+                    let line = ready!(this.poll_line(cx))?;
+                    this.state = match parse_size(&line)? {
+                        0 => DecodeState::Trailers,
+                        size => DecodeState::Data(size),
+                    };
+                }
+                DecodeState::Data(remaining) => {
+                    let available = ready!(Pin::new(&mut this.reader).poll_fill_buf(cx))?;
+                    if available.is_empty() {
+                        return Poll::Ready(Err(truncated()));
+                    }
+                    let len = available
+                        .len()
+                        .min(buf.len())
+                        .min(usize::try_from(remaining).unwrap_or(usize::MAX));
+                    buf[..len].copy_from_slice(&available[..len]);
+                    Pin::new(&mut this.reader).consume(len);
+                    this.state = match remaining - len as u64 {
+                        0 => DecodeState::DataEnd,
+                        remaining => DecodeState::Data(remaining),
+                    };
+                    return Poll::Ready(Ok(len));
+                }
+                DecodeState::DataEnd => {
+                    let line = ready!(this.poll_line(cx))?;
+                    if !line.is_empty() {
+                        return Poll::Ready(Err(invalid(
+                            "chunk data is longer than its chunk size",
+                        )));
+                    }
+                    this.state = DecodeState::Size;
+                }
+                DecodeState::Trailers => {
+                    let line = ready!(this.poll_line(cx))?;
+                    if line.is_empty() {
+                        this.state = DecodeState::Done;
+                        continue;
+                    }
+                    let (name, value) = parse_trailer(&line)?;
+                    this.trailers.append(name, value);
+                }
+                DecodeState::Done => return Poll::Ready(Ok(0)),
+            }
+        }
+    }
+}
+
+/// A writer encoding a body with `Transfer-Encoding: chunked`.
+///
+/// Every write is sent as its own chunk, which allows streaming a body of
+/// unknown length over a raw socket. Empty writes send nothing, so they
+/// can't end the body early. Closing the writer sends the terminating
+/// zero-length chunk and any trailers, and then closes the inner writer.
+///
+/// Each chunk is written out before the next write is accepted, so at most
+/// one chunk is held in memory at a time.
+#[derive(Debug)]
+pub struct ChunkedEncoder<W> {
+    writer: W,
+    /// Encoded bytes which have yet to be written to `writer`.
+    pending: Vec<u8>,
+    trailers: Trailers,
+    finished: bool,
+}
+
+impl<W: AsyncWrite + Unpin> ChunkedEncoder<W> {
+    /// Encode a chunked body written to `writer`.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            pending: Vec::new(),
+            trailers: Trailers::new(),
+            finished: false,
+        }
+    }
+
+    /// Set the trailers to send after the body once the writer is closed.
+    pub fn set_trailers(&mut self, trailers: Trailers) {
+        self.trailers = trailers;
+    }
+
+    /// Unwrap the writer.
+    ///
+    /// Encoded bytes which haven't been flushed yet are lost.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Write out all pending bytes.
+    fn poll_pending(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.pending.is_empty() {
+            let len = ready!(Pin::new(&mut self.writer).poll_write(cx, &self.pending))?;
+            if len == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.pending.drain(..len);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for ChunkedEncoder<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.finished {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "the chunked body has already been finished",
+            )));
+        }
+        ready!(this.poll_pending(cx))?;
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        let len = buf.len().min(MAX_CHUNK_LEN);
+        this.pending
+            .extend_from_slice(format!("{len:x}\r\n").as_bytes());
+        this.pending.extend_from_slice(&buf[..len]);
+        this.pending.extend_from_slice(b"\r\n");
+
+        // The chunk has been accepted, so a pending write can be picked up by
+        // the next write or flush.
+        let _ = this.poll_pending(cx)?;
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx))?;
+        Pin::new(&mut this.writer).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.finished {
+            ready!(this.poll_pending(cx))?;
+            this.pending.extend_from_slice(b"0\r\n");
+            for (name, value) in this.trailers.iter() {
+                this.pending.extend_from_slice(name.as_bytes());
+                this.pending.extend_from_slice(b": ");
+                this.pending.extend_from_slice(value);
+                this.pending.extend_from_slice(b"\r\n");
+            }
+            this.pending.extend_from_slice(b"\r\n");
+            this.finished = true;
+        }
+        ready!(this.poll_pending(cx))?;
+        Pin::new(&mut this.writer).poll_close(cx)
+    }
+}
+
+/// Parse a chunk size line, ignoring any chunk extensions.
+fn parse_size(line: &[u8]) -> io::Result<u64> {
+    let size = match line.iter().position(|&b| b == b';') {
+        Some(i) => &line[..i],
+        None => line,
+    };
+    let size = size.trim_ascii();
+    if size.is_empty() || !size.iter().all(u8::is_ascii_hexdigit) {
+        return Err(invalid("invalid chunk size in chunked body"));
+    }
+    // NOTE: the size is all hex digits, so it is valid UTF-8
+    let size = std::str::from_utf8(size).unwrap();
+    u64::from_str_radix(size, 16).map_err(|_| invalid("chunk size in chunked body is too large"))
+}
+
+/// Parse a `name: value` trailer line.
+fn parse_trailer(line: &[u8]) -> io::Result<(String, Vec<u8>)> {
+    let Some(i) = line.iter().position(|&b| b == b':') else {
+        return Err(invalid("invalid trailer in chunked body"));
+    };
+    let name = std::str::from_utf8(&line[..i])
+        .ok()
+        .filter(|name| !name.is_empty() && !name.contains(|c: char| c.is_ascii_whitespace()))
+        .ok_or_else(|| invalid("invalid trailer name in chunked body"))?;
+    Ok((name.to_owned(), line[i + 1..].trim_ascii().to_vec()))
+}
+
+fn invalid(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn truncated() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "the stream ended in the middle of a chunked body",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_lite::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    /// Decode `body`, returning the decoded bytes and the decoder.
+    fn decode(body: &[u8]) -> (io::Result<Vec<u8>>, ChunkedDecoder<&[u8]>) {
+        wasi_async_runtime::block_on(|_| async {
+            let mut decoder = ChunkedDecoder::new(body);
+            let mut out = Vec::new();
+            let res = decoder.read_to_end(&mut out).await.map(|_| out);
+            (res, decoder)
+        })
+    }
+
+    #[test]
+    fn decodes_multiple_chunks() {
+        let (body, decoder) = decode(b"5\r\nhello\r\n7;ext=1\r\n, world\r\n0\r\n\r\nrest");
+        assert_eq!(body.unwrap(), b"hello, world");
+        assert!(decoder.trailers().unwrap().is_empty());
+        // Bytes following the body are left alone
+        assert_eq!(decoder.into_inner(), b"rest");
+    }
+
+    #[test]
+    fn decodes_empty_body() {
+        let (body, _) = decode(b"0\r\n\r\n");
+        assert_eq!(body.unwrap(), b"");
+    }
+
+    #[test]
+    fn decodes_trailers() {
+        let (body, decoder) = decode(b"2\r\nhi\r\n0\r\nDigest: abc\r\nX-Done:  yes \r\n\r\n");
+        assert_eq!(body.unwrap(), b"hi");
+        let trailers = decoder.trailers().unwrap();
+        assert_eq!(trailers.get("digest").unwrap(), b"abc");
+        assert_eq!(trailers.get("x-done").unwrap(), b"yes");
+    }
+
+    #[test]
+    fn rejects_truncated_chunk() {
+        let (body, decoder) = decode(b"a\r\nhello");
+        assert_eq!(body.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        assert!(decoder.trailers().is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_size() {
+        let (body, _) = decode(b"zz\r\nhello\r\n0\r\n\r\n");
+        assert_eq!(body.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size(b"1a").unwrap(), 26);
+        assert_eq!(parse_size(b"FF ; name=value").unwrap(), 255);
+        assert!(parse_size(b"").is_err());
+        assert!(parse_size(b"-1").is_err());
+        assert!(parse_size(b"1 2").is_err());
+        assert!(parse_size(b"10000000000000000").is_err());
+    }
+
+    /// Encode `writes` followed by `trailers`, one chunk per write.
+    fn encode(writes: &[&[u8]], trailers: Trailers) -> Vec<u8> {
+        wasi_async_runtime::block_on(|_| async {
+            let mut encoder = ChunkedEncoder::new(Vec::new());
+            encoder.set_trailers(trailers);
+            for buf in writes {
+                encoder.write_all(buf).await.unwrap();
+            }
+            encoder.close().await.unwrap();
+            encoder.into_inner()
+        })
+    }
+
+    #[test]
+    fn encodes_one_chunk_per_write() {
+        let body = encode(&[b"hello", b"", b", world"], Trailers::new());
+        assert_eq!(body, b"5\r\nhello\r\n7\r\n, world\r\n0\r\n\r\n");
+    }
+
+    #[test]
+    fn encoded_body_round_trips() {
+        let mut trailers = Trailers::new();
+        trailers.append("digest", b"abc".to_vec());
+        let large = vec![b'x'; MAX_CHUNK_LEN * 2 + 1];
+        let body = encode(&[b"small", &large], trailers);
+
+        let (decoded, decoder) = decode(&body);
+        let decoded = decoded.unwrap();
+        assert_eq!(decoded.len(), 5 + large.len());
+        assert!(decoded.starts_with(b"small") && decoded.ends_with(&large));
+        assert_eq!(decoder.trailers().unwrap().get("digest").unwrap(), b"abc");
+    }
+
+    #[test]
+    fn rejects_writes_after_close() {
+        wasi_async_runtime::block_on(|_| async {
+            let mut encoder = ChunkedEncoder::new(Vec::new());
+            encoder.close().await.unwrap();
+            let err = encoder.write_all(b"late").await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+            // Closing again doesn't send a second terminating chunk
+            encoder.close().await.unwrap();
+            assert_eq!(encoder.into_inner(), b"0\r\n\r\n");
+        });
+    }
+}
@@ -0,0 +1,363 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+use url::Url;
+use wasi::clocks::wall_clock;
+use wasi_async_runtime::monotonic::{self, Instant};
+
+/// A cookie stored in a [`CookieJar`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    name: String,
+    value: String,
+    domain: String,
+    /// Whether the cookie only matches `domain` itself, and not its
+    /// subdomains. This is the case when `Set-Cookie` had no `Domain`.
+    host_only: bool,
+    path: String,
+    secure: bool,
+    expires: Option<Instant>,
+}
+
+impl Cookie {
+    /// Get the name of the cookie.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the value of the cookie.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Get the domain the cookie is sent to.
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    /// Get the path the cookie is sent to, along with everything below it.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Whether the cookie is only sent over `https`.
+    pub fn secure(&self) -> bool {
+        self.secure
+    }
+
+    /// Whether the cookie has expired at `now`.
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    /// Whether the cookie should be sent along with a request to `url`.
+    fn matches(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let domain_matches = host.eq_ignore_ascii_case(&self.domain)
+            || (!self.host_only && is_subdomain(host, &self.domain));
+        domain_matches
+            && path_matches(url.path(), &self.path)
+            && (!self.secure || url.scheme() == "https")
+    }
+
+    /// Parse a `Set-Cookie` header received in response to a request to
+    /// `url`.
+    ///
+    /// Returns `None` if the header is malformed, or sets a cookie for a
+    /// domain `url` doesn't belong to.
+    fn parse(header: &str, url: &Url) -> Option<Self> {
+        let host = url.host_str()?;
+        let mut attributes = header.split(';');
+        let (name, value) = attributes.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+
+        let mut cookie = Cookie {
+            name: name.to_owned(),
+            value: value.trim().trim_matches('"').to_owned(),
+            domain: host.to_ascii_lowercase(),
+            host_only: true,
+            path: default_path(url),
+            secure: false,
+            expires: None,
+        };
+
+        // `Max-Age` takes precedence over `Expires`, regardless of the order
+        // they appear in.
+        let mut max_age = None;
+        let mut expires = None;
+        for attribute in attributes {
+            let (key, value) = match attribute.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => (attribute.trim(), ""),
+            };
+            if key.eq_ignore_ascii_case("domain") {
+                let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                if domain.is_empty() {
+                    continue;
+                }
+                if !host.eq_ignore_ascii_case(&domain) && !is_subdomain(host, &domain) {
+                    return None;
+                }
+                cookie.domain = domain;
+                cookie.host_only = false;
+            } else if key.eq_ignore_ascii_case("path") {
+                if value.starts_with('/') {
+                    cookie.path = value.to_owned();
+                }
+            } else if key.eq_ignore_ascii_case("secure") {
+                cookie.secure = true;
+            } else if key.eq_ignore_ascii_case("max-age") {
+                max_age = value.parse::<i64>().ok().or(max_age);
+            } else if key.eq_ignore_ascii_case("expires") {
+                expires = parse_http_date(value).or(expires);
+            }
+        }
+
+        let now = monotonic::now();
+        cookie.expires = match (max_age, expires) {
+            (Some(secs), _) if secs <= 0 => Some(now),
+            (Some(secs), _) => now.checked_add(Duration::from_secs(secs as u64)),
+            (None, Some(expires)) => {
+                let wall_now = wall_clock::now().seconds as i64;
+                match expires - wall_now {
+                    secs if secs <= 0 => Some(now),
+                    secs => now.checked_add(Duration::from_secs(secs as u64)),
+                }
+            }
+            (None, None) => None,
+        };
+        Some(cookie)
+    }
+}
+
+/// Stores cookies set by responses, and attaches them to later requests.
+///
+/// Cloning the jar shares the cookies between the clones, so the same jar can
+/// be inspected while it is in use by a [`Client`](crate::Client). Expired
+/// cookies are removed whenever the jar is accessed.
+#[derive(Debug, Clone, Default)]
+pub struct CookieJar {
+    cookies: Rc<RefCell<Vec<Cookie>>>,
+}
+
+impl CookieJar {
+    /// Create an empty cookie jar.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get all cookies which haven't expired.
+    pub fn cookies(&self) -> Vec<Cookie> {
+        self.purge();
+        self.cookies.borrow().clone()
+    }
+
+    /// Remove all cookies.
+    pub fn clear(&self) {
+        self.cookies.borrow_mut().clear();
+    }
+
+    /// Store the cookies from the `Set-Cookie` headers of a response to a
+    /// request to `url`.
+    ///
+    /// A cookie replaces any existing cookie with the same name, domain and
+    /// path. Cookies which have already expired remove the cookie they
+    /// replace.
+    pub(crate) fn store<'a>(&self, url: &Url, headers: impl Iterator<Item = &'a Vec<u8>>) {
+        let mut cookies = self.cookies.borrow_mut();
+        for header in headers {
+            let Ok(header) = std::str::from_utf8(header) else {
+                continue;
+            };
+            let Some(cookie) = Cookie::parse(header, url) else {
+                continue;
+            };
+            cookies.retain(|existing| {
+                existing.name != cookie.name
+                    || existing.domain != cookie.domain
+                    || existing.path != cookie.path
+            });
+            cookies.push(cookie);
+        }
+        drop(cookies);
+        self.purge();
+    }
+
+    /// Get the value of the `Cookie` header for a request to `url`, if any
+    /// cookies match it.
+    ///
+    /// Cookies with longer paths are listed first.
+    pub(crate) fn header_for(&self, url: &Url) -> Option<String> {
+        self.purge();
+        let cookies = self.cookies.borrow();
+        let mut matching: Vec<_> = cookies.iter().filter(|c| c.matches(url)).collect();
+        if matching.is_empty() {
+            return None;
+        }
+        matching.sort_by_key(|cookie| std::cmp::Reverse(cookie.path.len()));
+        let pairs: Vec<_> = matching
+            .iter()
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect();
+        Some(pairs.join("; "))
+    }
+
+    /// Remove all expired cookies.
+    fn purge(&self) {
+        let now = monotonic::now();
+        self.cookies
+            .borrow_mut()
+            .retain(|cookie| !cookie.is_expired(now));
+    }
+}
+
+/// Whether `host` is a subdomain of `domain`.
+fn is_subdomain(host: &str, domain: &str) -> bool {
+    host.len() > domain.len()
+        && host[host.len() - domain.len()..].eq_ignore_ascii_case(domain)
+        && host.as_bytes()[host.len() - domain.len() - 1] == b'.'
+        && host.parse::<std::net::IpAddr>().is_err()
+}
+
+/// Whether a request path falls under a cookie path, per RFC 6265.
+fn path_matches(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/')
+                || request_path.as_bytes().get(cookie_path.len()) == Some(&b'/')))
+}
+
+/// The path a cookie is sent to if it doesn't set one: the directory of the
+/// request path.
+fn default_path(url: &Url) -> String {
+    match url.path().rfind('/') {
+        Some(0) | None => "/".to_owned(),
+        Some(index) => url.path()[..index].to_owned(),
+    }
+}
+
+/// Parse an HTTP date such as `Wed, 21 Oct 2015 07:28:00 GMT` into seconds
+/// since the Unix epoch.
+fn parse_http_date(date: &str) -> Option<i64> {
+    // Cookie dates are often written with dashes, as in `21-Oct-2015`
+    let date = date.replace('-', " ");
+    let mut parts = date.split_whitespace();
+    let _weekday = parts.next()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    let month = match parts.next()?.get(..3)?.to_ascii_lowercase().as_str() {
+        "jan" => 1,
+        "feb" => 2,
+        "mar" => 3,
+        "apr" => 4,
+        "may" => 5,
+        "jun" => 6,
+        "jul" => 7,
+        "aug" => 8,
+        "sep" => 9,
+        "oct" => 10,
+        "nov" => 11,
+        "dec" => 12,
+        _ => return None,
+    };
+    let mut year: i64 = parts.next()?.parse().ok()?;
+    if year < 100 {
+        year += if year < 70 { 2000 } else { 1900 };
+    }
+    let mut time = parts.next()?.split(':').map(|part| part.parse::<i64>());
+    let hours = time.next()?.ok()?;
+    let minutes = time.next()?.ok()?;
+    let seconds = time.next()?.ok()?;
+
+    // Count the days since the epoch, using Howard Hinnant's
+    // `days_from_civil` algorithm.
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    Some(days * 86400 + hours * 3600 + minutes * 60 + seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cookie(domain: &str, host_only: bool, path: &str, secure: bool) -> Cookie {
+        Cookie {
+            name: "id".to_owned(),
+            value: "1".to_owned(),
+            domain: domain.to_owned(),
+            host_only,
+            path: path.to_owned(),
+            secure,
+            expires: None,
+        }
+    }
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    #[test]
+    fn matches_domain() {
+        let host_only = cookie("example.com", true, "/", false);
+        assert!(host_only.matches(&url("http://example.com/")));
+        assert!(host_only.matches(&url("http://EXAMPLE.com/")));
+        assert!(!host_only.matches(&url("http://www.example.com/")));
+
+        let domain = cookie("example.com", false, "/", false);
+        assert!(domain.matches(&url("http://www.example.com/")));
+        assert!(!domain.matches(&url("http://badexample.com/")));
+    }
+
+    #[test]
+    fn matches_path_and_scheme() {
+        let cookie = cookie("example.com", true, "/docs", true);
+        assert!(cookie.matches(&url("https://example.com/docs")));
+        assert!(cookie.matches(&url("https://example.com/docs/web")));
+        assert!(!cookie.matches(&url("https://example.com/docsweb")));
+        assert!(!cookie.matches(&url("http://example.com/docs")));
+    }
+
+    #[test]
+    fn subdomains() {
+        assert!(is_subdomain("a.example.com", "example.com"));
+        assert!(is_subdomain("a.b.EXAMPLE.com", "example.com"));
+        assert!(!is_subdomain("example.com", "example.com"));
+        assert!(!is_subdomain("badexample.com", "example.com"));
+        assert!(!is_subdomain("1.0.0.1", "0.0.1"));
+    }
+
+    #[test]
+    fn paths() {
+        assert!(path_matches("/", "/"));
+        assert!(path_matches("/docs/", "/docs/"));
+        assert!(path_matches("/docs/web", "/docs/"));
+        assert!(path_matches("/docs/web", "/docs"));
+        assert!(!path_matches("/docsweb", "/docs"));
+        assert!(!path_matches("/", "/docs"));
+
+        assert_eq!(default_path(&url("http://example.com/a/b")), "/a");
+        assert_eq!(default_path(&url("http://example.com/a")), "/");
+        assert_eq!(default_path(&url("http://example.com/")), "/");
+    }
+
+    #[test]
+    fn http_dates() {
+        let expected = Some(1445412480);
+        assert_eq!(parse_http_date("Wed, 21 Oct 2015 07:28:00 GMT"), expected);
+        assert_eq!(parse_http_date("Wed, 21-Oct-2015 07:28:00 GMT"), expected);
+        assert_eq!(parse_http_date("Wed, 21-Oct-15 07:28:00 GMT"), expected);
+        assert_eq!(parse_http_date("Thu, 01 Jan 1970 00:00:00 GMT"), Some(0));
+        assert_eq!(parse_http_date("Wed, 21 Foo 2015 07:28:00 GMT"), None);
+        assert_eq!(parse_http_date("yesterday"), None);
+    }
+}
@@ -1,5 +1,110 @@
+use std::{error, fmt, io};
+use wasi::http::types::ErrorCode;
+
 /// The `wasi-http-client` error type.
-pub type Error = wasi::http::types::ErrorCode;
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The connection to the server couldn't be established, or was lost.
+    Connect(ErrorCode),
+    /// The TLS connection to the server couldn't be established.
+    Tls(ErrorCode),
+    /// The request didn't complete in time.
+    Timeout,
+    /// The request was redirected more often than its
+    /// [`RedirectPolicy`](crate::RedirectPolicy) allows.
+    TooManyRedirects,
+    /// The URL of the request is malformed, or can't be requested.
+    InvalidUrl(String),
+    /// A header of the request is malformed, or can't be set.
+    InvalidHeader(String),
+    /// The server or the host rejected the request, or sent an invalid
+    /// response.
+    Protocol(ErrorCode),
+    /// Reading or writing a body failed.
+    Body(io::Error),
+    /// The response body is larger than the request's
+    /// [maximum body size](crate::Request::set_max_body_size), in bytes.
+    BodyTooLarge(usize),
+    /// The response has more headers, or more header bytes, than the
+    /// [`Client`](crate::Client) allows.
+    HeadersTooLarge,
+    /// Writing a body out to somewhere else failed, such as in
+    /// [`Response::copy_to`](crate::Response::copy_to).
+    Write(io::Error),
+    /// The request was cancelled through its
+    /// [`CancellationToken`](wasi_async_runtime::CancellationToken).
+    Cancelled,
+    /// The request asks for something the host doesn't support.
+    Unsupported(&'static str),
+}
+
+impl From<ErrorCode> for Error {
+    /// Sort the errors reported by `wasi:http` into the kind of failure they
+    /// describe.
+    fn from(code: ErrorCode) -> Self {
+        match code {
+            ErrorCode::DnsTimeout
+            | ErrorCode::ConnectionTimeout
+            | ErrorCode::ConnectionReadTimeout
+            | ErrorCode::ConnectionWriteTimeout
+            | ErrorCode::HttpResponseTimeout => Self::Timeout,
+            ErrorCode::DnsError(_)
+            | ErrorCode::DestinationNotFound
+            | ErrorCode::DestinationUnavailable
+            | ErrorCode::DestinationIpProhibited
+            | ErrorCode::DestinationIpUnroutable
+            | ErrorCode::ConnectionRefused
+            | ErrorCode::ConnectionTerminated
+            | ErrorCode::ConnectionLimitReached => Self::Connect(code),
+            ErrorCode::TlsProtocolError
+            | ErrorCode::TlsCertificateError
+            | ErrorCode::TlsAlertReceived(_) => Self::Tls(code),
+            code => Self::Protocol(code),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::Body(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Connect(code) => write!(f, "failed to connect to the server: {code}"),
+            Self::Tls(code) => write!(f, "failed to establish a TLS connection: {code}"),
+            Self::Timeout => f.write_str("the request timed out"),
+            Self::TooManyRedirects => f.write_str("the request was redirected too many times"),
+            Self::InvalidUrl(reason) => write!(f, "invalid URL: {reason}"),
+            Self::InvalidHeader(reason) => write!(f, "invalid header: {reason}"),
+            Self::Protocol(code) => write!(f, "the request failed: {code}"),
+            Self::Body(err) => write!(f, "failed to transfer the body: {err}"),
+            Self::BodyTooLarge(max) => {
+                write!(
+                    f,
+                    "the response body is larger than the limit of {max} bytes"
+                )
+            }
+            Self::HeadersTooLarge => f.write_str("the response headers exceed the limit"),
+            Self::Write(err) => write!(f, "failed to write out the body: {err}"),
+            Self::Cancelled => f.write_str("the request was cancelled"),
+            Self::Unsupported(what) => write!(f, "{what} is not supported by the host"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Connect(code) | Self::Tls(code) | Self::Protocol(code) => Some(code),
+            Self::Body(err) | Self::Write(err) => Some(err),
+            _ => None,
+        }
+    }
+}
 
 /// The `wasi-http-client` result type.
 pub type Result<T> = std::result::Result<T, Error>;
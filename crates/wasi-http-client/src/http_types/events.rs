@@ -0,0 +1,243 @@
+use futures_lite::{ready, AsyncBufRead, Stream};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use super::BodyReader;
+
+/// An event sent by the server in a `text/event-stream` body.
+///
+/// See [`Response::events`](crate::Response::events).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    event: String,
+    data: String,
+    id: Option<String>,
+    retry: Option<Duration>,
+}
+
+impl Event {
+    /// The type of the event, which is `message` unless the server set one.
+    pub fn event(&self) -> &str {
+        &self.event
+    }
+
+    /// The data of the event, with the lines of multi-line data joined by
+    /// newlines.
+    pub fn data(&self) -> &str {
+        &self.data
+    }
+
+    /// The last event ID the server sent, as of this event.
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// The reconnection time the server asked for, as of this event.
+    pub fn retry(&self) -> Option<Duration> {
+        self.retry
+    }
+}
+
+/// A stream of the server-sent events in a response body.
+///
+/// See [`Response::events`](crate::Response::events).
+#[derive(Debug)]
+pub struct Events {
+    body: BodyReader,
+    /// The line being read, which may span several reads.
+    line: Vec<u8>,
+    /// Skip a `\n` at the start of the next read, since the last line ended in
+    /// a `\r`.
+    skip_lf: bool,
+    /// Whether the first line has been read, so a leading BOM can be removed.
+    started: bool,
+    event: String,
+    data: String,
+    last_event_id: Option<String>,
+    retry: Option<Duration>,
+}
+
+impl Events {
+    pub(crate) fn new(body: BodyReader) -> Self {
+        Self {
+            body,
+            line: Vec::new(),
+            skip_lf: false,
+            started: false,
+            event: String::new(),
+            data: String::new(),
+            last_event_id: None,
+            retry: None,
+        }
+    }
+
+    /// The ID of the last event the server sent.
+    ///
+    /// When reconnecting, send this as the `Last-Event-ID` header so the
+    /// server can pick up where it left off.
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.last_event_id.as_deref()
+    }
+
+    /// The reconnection time the server asked for.
+    pub fn retry(&self) -> Option<Duration> {
+        self.retry
+    }
+
+    /// Read the next line, without its line break.
+    ///
+    /// Returns `None` once the body has ended. A final line without a line
+    /// break is dropped, since it can't complete an event.
+    fn poll_line(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<Option<String>>> {
+        loop {
+            let available = ready!(Pin::new(&mut self.body).poll_fill_buf(cx))?;
+            if available.is_empty() {
+                return Poll::Ready(Ok(None));
+            }
+            if std::mem::take(&mut self.skip_lf) && available[0] == b'\n' {
+                Pin::new(&mut self.body).consume(1);
+                continue;
+            }
+            let end = available.iter().position(|&b| b == b'\n' || b == b'\r');
+            let len = end.map_or(available.len(), |i| i + 1);
+            self.line.extend_from_slice(&available[..len]);
+            Pin::new(&mut self.body).consume(len);
+            if end.is_none() {
+                continue;
+            }
+
+            // NOTE: the line always ends in the line break we found
+            if self.line.pop() == Some(b'\r') {
+                self.skip_lf = true;
+            }
+            let mut line = std::mem::take(&mut self.line);
+            if !std::mem::replace(&mut self.started, true) && line.starts_with(b"\xef\xbb\xbf") {
+                line.drain(..3);
+            }
+            let line = String::from_utf8(line)
+                .unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into_owned());
+            return Poll::Ready(Ok(Some(line)));
+        }
+    }
+
+    /// Process a line which isn't blank.
+    fn process_field(&mut self, line: &str) {
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event = value.to_owned(),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+            }
+            "id" if !value.contains('\0') => self.last_event_id = Some(value.to_owned()),
+            "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                if let Ok(millis) = value.parse() {
+                    self.retry = Some(Duration::from_millis(millis));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Stream for Events {
+    type Item = crate::Result<Event>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            let line = match ready!(this.poll_line(cx)) {
+                Ok(Some(line)) => line,
+                // An event which wasn't completed by a blank line is dropped
+                Ok(None) => return Poll::Ready(None),
+                Err(err) => return Poll::Ready(Some(Err(err.into()))),
+            };
+            if line.starts_with(':') {
+                continue;
+            }
+            if !line.is_empty() {
+                this.process_field(&line);
+                continue;
+            }
+
+            // A blank line dispatches the event, if it has any data
+            let event = std::mem::take(&mut this.event);
+            if this.data.is_empty() {
+                continue;
+            }
+            let mut data = std::mem::take(&mut this.data);
+            data.pop();
+            return Poll::Ready(Some(Ok(Event {
+                event: if event.is_empty() {
+                    "message".to_owned()
+                } else {
+                    event
+                },
+                data,
+                id: this.last_event_id.clone(),
+                retry: this.retry,
+            })));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_lite::StreamExt;
+
+    use super::*;
+
+    /// Parse `body` into its events, along with the final state of the
+    /// stream.
+    fn parse(body: &str) -> (Vec<Event>, Events) {
+        let mut events = Events::new(BodyReader::from_bytes(body.as_bytes().to_vec()));
+        let parsed = wasi_async_runtime::block_on(|_| async {
+            let mut parsed = Vec::new();
+            while let Some(event) = events.next().await {
+                parsed.push(event.unwrap());
+            }
+            parsed
+        });
+        (parsed, events)
+    }
+
+    #[test]
+    fn parses_events() {
+        let body = "\u{feff}: a comment\n\
+                    data: first\n\n\
+                    event: update\r\nid: 1\r\ndata: line one\r\ndata:line two\r\n\r\n\
+                    retry: 3000\rdata\r\r\
+                    event: ignored\n\n\
+                    data: unfinished";
+        let (events, stream) = parse(body);
+        let parsed: Vec<_> = events
+            .iter()
+            .map(|event| (event.event(), event.data(), event.id()))
+            .collect();
+        assert_eq!(
+            parsed,
+            [
+                ("message", "first", None),
+                ("update", "line one\nline two", Some("1")),
+                ("message", "", Some("1")),
+            ]
+        );
+        assert_eq!(events[0].retry(), None);
+        assert_eq!(events[2].retry(), Some(Duration::from_secs(3)));
+        assert_eq!(stream.last_event_id(), Some("1"));
+        assert_eq!(stream.retry(), Some(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn ignores_invalid_fields() {
+        let (events, stream) = parse("id: a\0b\nretry: 1.5\nunknown: x\ndata: ok\n\n");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].data(), "ok");
+        assert_eq!(stream.last_event_id(), None);
+        assert_eq!(stream.retry(), None);
+    }
+}
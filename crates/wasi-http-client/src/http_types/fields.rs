@@ -1,4 +1,4 @@
-use std::{borrow::Cow, collections::HashMap};
+use std::borrow::Cow;
 use wasi::http::types::{Fields as WasiFields, HeaderError};
 
 /// A type alias for [`Fields`] when used as HTTP headers.
@@ -14,30 +14,132 @@ pub type FieldName = Cow<'static, str>;
 pub type FieldValue = Vec<u8>;
 
 /// HTTP Fields which can be used as either trailers or headers.
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Fields(pub(crate) HashMap<FieldName, Vec<FieldValue>>);
+///
+/// Field names are compared case-insensitively, and a name may have several
+/// values, such as multiple `Set-Cookie` headers. Fields are kept in the order
+/// they were inserted in.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Fields(Vec<(FieldName, FieldValue)>);
+
+impl Fields {
+    /// Create an empty set of fields.
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    /// Get the first value of a field.
+    pub fn get(&self, name: &str) -> Option<&FieldValue> {
+        self.0
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    /// Get all values of a field, in the order they were inserted in.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a FieldValue> + 'a {
+        self.0
+            .iter()
+            .filter(move |(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    /// Whether the fields contain a field with this name.
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Add a value to a field, keeping any existing values.
+    pub fn append(&mut self, name: impl Into<FieldName>, value: impl Into<FieldValue>) {
+        self.0.push((name.into(), value.into()));
+    }
+
+    /// Set the value of a field, replacing any existing values.
+    pub fn set(&mut self, name: impl Into<FieldName>, value: impl Into<FieldValue>) {
+        let name = name.into();
+        self.remove(&name);
+        self.0.push((name, value.into()));
+    }
+
+    /// Remove all values of a field.
+    pub fn remove(&mut self, name: &str) {
+        self.0.retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+    }
+
+    /// Iterate over all fields, in the order they were inserted in.
+    ///
+    /// Fields with several values are yielded once for each value.
+    pub fn iter(&self) -> impl Iterator<Item = (&FieldName, &FieldValue)> {
+        self.0.iter().map(|(name, value)| (name, value))
+    }
+
+    /// Get the number of field values.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether there are no fields.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
 
 impl From<WasiFields> for Fields {
     fn from(wasi_fields: WasiFields) -> Self {
-        let mut output = HashMap::new();
-        for (key, value) in wasi_fields.entries() {
-            let field_name = key.into();
-            let field_list: &mut Vec<_> = output.entry(field_name).or_default();
-            field_list.push(value);
-        }
-        Self(output)
+        let entries = wasi_fields
+            .entries()
+            .into_iter()
+            .map(|(key, value)| (key.into(), value))
+            .collect();
+        Self(entries)
     }
 }
 
 impl TryFrom<Fields> for WasiFields {
     type Error = HeaderError;
     fn try_from(fields: Fields) -> Result<Self, Self::Error> {
-        let mut list = Vec::with_capacity(fields.0.capacity());
-        for (name, values) in fields.0.into_iter() {
-            for value in values {
-                list.push((name.clone().into_owned(), value));
-            }
-        }
-        Ok(WasiFields::from_list(&list)?)
+        let list: Vec<_> = fields
+            .0
+            .into_iter()
+            .map(|(name, value)| (name.into_owned(), value))
+            .collect();
+        WasiFields::from_list(&list)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_is_case_insensitive() {
+        let mut headers = Headers::new();
+        headers.set("Content-Type", "text/plain");
+        assert_eq!(headers.get("content-type").unwrap(), b"text/plain");
+        assert!(headers.contains("CONTENT-TYPE"));
+        headers.remove("content-TYPE");
+        assert!(headers.is_empty());
+    }
+
+    #[test]
+    fn keeps_multiple_values() {
+        let mut headers = Headers::new();
+        headers.append("Set-Cookie", "a=1");
+        headers.append("set-cookie", "b=2");
+        let values: Vec<_> = headers.get_all("set-cookie").map(Vec::as_slice).collect();
+        assert_eq!(values, [b"a=1", b"b=2"]);
+        assert_eq!(headers.get("SET-COOKIE").unwrap(), b"a=1");
+        assert_eq!(headers.len(), 2);
+    }
+
+    #[test]
+    fn set_replaces_all_values() {
+        let mut headers = Headers::new();
+        headers.append("accept", "text/html");
+        headers.append("x-other", "1");
+        headers.append("Accept", "text/plain");
+        headers.set("ACCEPT", "*/*");
+        let fields: Vec<_> = headers.iter().map(|(name, _)| name.as_ref()).collect();
+        assert_eq!(fields, ["x-other", "ACCEPT"]);
+        assert_eq!(headers.get("accept").unwrap(), b"*/*");
     }
 }
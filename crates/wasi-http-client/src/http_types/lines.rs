@@ -0,0 +1,119 @@
+use futures_lite::{ready, AsyncBufRead, Stream};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use super::BodyReader;
+
+/// A stream of the lines in a response body.
+///
+/// See [`Response::lines`](crate::Response::lines).
+#[derive(Debug)]
+pub struct Lines {
+    body: BodyReader,
+    /// The line being read, which may span several reads.
+    line: Vec<u8>,
+    done: bool,
+}
+
+impl Lines {
+    pub(crate) fn new(body: BodyReader) -> Self {
+        Self {
+            body,
+            line: Vec::new(),
+            done: false,
+        }
+    }
+}
+
+impl Stream for Lines {
+    type Item = crate::Result<String>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(None);
+        }
+        loop {
+            let available = match ready!(Pin::new(&mut this.body).poll_fill_buf(cx)) {
+                Ok(available) => available,
+                Err(err) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(err.into())));
+                }
+            };
+
+            // The body ended: hand out the last line if it had no line break
+            if available.is_empty() {
+                this.done = true;
+                if this.line.is_empty() {
+                    return Poll::Ready(None);
+                }
+                return Poll::Ready(Some(decode(std::mem::take(&mut this.line))));
+            }
+
+            let (len, done) = match available.iter().position(|&b| b == b'\n') {
+                Some(i) => (i + 1, true),
+                None => (available.len(), false),
+            };
+            this.line.extend_from_slice(&available[..len]);
+            Pin::new(&mut this.body).consume(len);
+            if done {
+                let mut line = std::mem::take(&mut this.line);
+                line.pop();
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+                return Poll::Ready(Some(decode(line)));
+            }
+        }
+    }
+}
+
+/// Decode a line as UTF-8. Invalid lines fail on their own, so the lines
+/// after them can still be read.
+fn decode(line: Vec<u8>) -> crate::Result<String> {
+    String::from_utf8(line).map_err(|err| {
+        crate::Error::Body(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("line isn't valid UTF-8: {err}"),
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_lite::StreamExt;
+
+    use super::*;
+
+    /// Split `body` into its lines.
+    fn lines(body: &[u8]) -> Vec<crate::Result<String>> {
+        let lines = Lines::new(BodyReader::from_bytes(body.to_vec()));
+        wasi_async_runtime::block_on(|_| lines.collect())
+    }
+
+    #[test]
+    fn splits_ndjson() {
+        let body = b"{\"id\":1}\n{\"id\":2}\r\n\n{\"id\":3}";
+        let lines: Vec<_> = lines(body).into_iter().map(Result::unwrap).collect();
+        assert_eq!(lines, [r#"{"id":1}"#, r#"{"id":2}"#, "", r#"{"id":3}"#]);
+    }
+
+    #[test]
+    fn trailing_line_break_ends_the_body() {
+        assert!(lines(b"").is_empty());
+        let lines: Vec<_> = lines(b"one\n").into_iter().map(Result::unwrap).collect();
+        assert_eq!(lines, ["one"]);
+    }
+
+    #[test]
+    fn invalid_line_doesnt_end_the_stream() {
+        let mut lines = lines(b"one\n\xff\xfe\nthree\n").into_iter();
+        assert_eq!(lines.next().unwrap().unwrap(), "one");
+        let err = lines.next().unwrap().unwrap_err();
+        assert!(err.to_string().contains("UTF-8"), "{err}");
+        assert_eq!(lines.next().unwrap().unwrap(), "three");
+        assert!(lines.next().is_none());
+    }
+}
@@ -1,7 +1,7 @@
 use wasi::http::types::Method as WasiMethod;
 
 /// The method for the HTTP request
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[non_exhaustive]
 pub enum Method {
     /// The GET method requests transfer of a current selected representation
@@ -69,3 +69,37 @@ impl From<WasiMethod> for Method {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_to_wasi_methods() {
+        assert!(matches!(WasiMethod::from(Method::Get), WasiMethod::Get));
+        assert!(matches!(WasiMethod::from(Method::Patch), WasiMethod::Patch));
+        assert!(matches!(
+            WasiMethod::from(Method::Other("PURGE".to_owned())),
+            WasiMethod::Other(method) if method == "PURGE"
+        ));
+    }
+
+    #[test]
+    fn round_trips_through_wasi() {
+        let methods = [
+            Method::Get,
+            Method::Head,
+            Method::Post,
+            Method::Put,
+            Method::Delete,
+            Method::Connect,
+            Method::Options,
+            Method::Trace,
+            Method::Patch,
+            Method::Other("PURGE".to_owned()),
+        ];
+        for method in methods {
+            assert_eq!(Method::from(WasiMethod::from(method.clone())), method);
+        }
+    }
+}
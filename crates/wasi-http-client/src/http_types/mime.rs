@@ -0,0 +1,158 @@
+use std::fmt;
+
+/// A media type, as found in `Content-Type` and `Accept` headers.
+///
+/// The type, subtype and parameter names are compared case-insensitively,
+/// and are stored in lowercase.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mime {
+    type_: String,
+    subtype: String,
+    params: Vec<(String, String)>,
+}
+
+impl Mime {
+    /// Parse a media type such as `text/plain; charset=utf-8`.
+    ///
+    /// Returns `None` if the type or subtype is missing. Malformed
+    /// parameters are skipped, and quoted parameter values are unquoted.
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut parts = s.split(';');
+        let (type_, subtype) = parts.next()?.trim().split_once('/')?;
+        let (type_, subtype) = (type_.trim(), subtype.trim());
+        if !is_token(type_) || !is_token(subtype) {
+            return None;
+        }
+
+        let params = parts
+            .filter_map(|param| {
+                let (name, value) = param.split_once('=')?;
+                let name = name.trim();
+                if !is_token(name) {
+                    return None;
+                }
+                let value = value.trim();
+                let value = match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+                    Some(quoted) => unquote(quoted),
+                    None => value.to_owned(),
+                };
+                Some((name.to_ascii_lowercase(), value))
+            })
+            .collect();
+
+        Some(Self {
+            type_: type_.to_ascii_lowercase(),
+            subtype: subtype.to_ascii_lowercase(),
+            params,
+        })
+    }
+
+    /// The top-level type, such as `text` in `text/plain`.
+    pub fn type_(&self) -> &str {
+        &self.type_
+    }
+
+    /// The subtype, such as `plain` in `text/plain`.
+    pub fn subtype(&self) -> &str {
+        &self.subtype
+    }
+
+    /// Get the value of a parameter.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The `charset` parameter, if there is one.
+    pub fn charset(&self) -> Option<&str> {
+        self.param("charset")
+    }
+
+    /// Whether this is JSON: `application/json`, or a type with a `+json`
+    /// suffix such as `application/problem+json`.
+    pub fn is_json(&self) -> bool {
+        self.subtype == "json" || self.subtype.ends_with("+json")
+    }
+
+    /// Whether this is a `text/*` type.
+    pub fn is_text(&self) -> bool {
+        self.type_ == "text"
+    }
+}
+
+impl fmt::Display for Mime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.type_, self.subtype)?;
+        for (name, value) in &self.params {
+            if is_token(value) {
+                write!(f, "; {name}={value}")?;
+            } else {
+                let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+                write!(f, "; {name}=\"{value}\"")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `s` is a non-empty HTTP token.
+pub(super) fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+/// Remove the backslash escapes from a quoted string.
+fn unquote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_type_and_params() {
+        let mime =
+            Mime::parse(" Text/HTML ; Charset=\"UTF-8\"; q=\"a \\\"b\\\"\"; bad param=1").unwrap();
+        assert_eq!(mime.type_(), "text");
+        assert_eq!(mime.subtype(), "html");
+        assert_eq!(mime.charset(), Some("UTF-8"));
+        assert_eq!(mime.param("Q"), Some("a \"b\""));
+        assert_eq!(mime.param("bad param"), None);
+        assert!(mime.is_text());
+        assert!(!mime.is_json());
+        assert_eq!(
+            mime.to_string(),
+            "text/html; charset=UTF-8; q=\"a \\\"b\\\"\""
+        );
+    }
+
+    #[test]
+    fn rejects_missing_subtype() {
+        assert_eq!(Mime::parse("text"), None);
+        assert_eq!(Mime::parse("text/"), None);
+        assert_eq!(Mime::parse("/plain"), None);
+        assert_eq!(Mime::parse("te xt/plain"), None);
+    }
+
+    #[test]
+    fn detects_json() {
+        assert!(Mime::parse("application/json").unwrap().is_json());
+        assert!(Mime::parse("application/problem+json; charset=utf-8")
+            .unwrap()
+            .is_json());
+        assert!(!Mime::parse("application/jsonl").unwrap().is_json());
+        assert!(!Mime::parse("text/plain").unwrap().is_json());
+    }
+}
@@ -1,14 +1,34 @@
+pub use body::Body;
+pub use body_reader::BodyReader;
+pub use chunked::{ChunkedDecoder, ChunkedEncoder};
+pub use cookie_jar::{Cookie, CookieJar};
 pub use error::{Error, Result};
+pub use events::{Event, Events};
 pub use fields::{FieldName, FieldValue, Fields, Headers, Trailers};
+pub use lines::Lines;
 pub use method::Method;
+pub use mime::Mime;
+pub use multipart::Multipart;
+pub use redirect::RedirectPolicy;
 pub use request::Request;
 pub use response::Response;
+pub use retry::RetryPolicy;
 pub use status_code::StatusCode;
 
+mod body;
+mod body_reader;
+mod chunked;
+mod cookie_jar;
 mod error;
+mod events;
 mod fields;
+mod lines;
 mod method;
+mod mime;
+mod multipart;
+mod redirect;
 mod request;
 mod response;
+mod retry;
 
 mod status_code;
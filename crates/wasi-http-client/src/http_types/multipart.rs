@@ -0,0 +1,232 @@
+use futures_lite::io::Cursor;
+use futures_lite::AsyncRead;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use super::Body;
+
+/// A `multipart/form-data` body.
+///
+/// Parts are streamed into the request in order, so large files don't need
+/// to be held in memory.
+pub struct Multipart {
+    parts: Vec<Part>,
+}
+
+struct Part {
+    name: String,
+    filename: Option<String>,
+    content_type: Option<String>,
+    content: PartContent,
+}
+
+enum PartContent {
+    Text(String),
+    Reader(Pin<Box<dyn AsyncRead>>),
+}
+
+impl Multipart {
+    /// Create an empty form.
+    pub fn new() -> Self {
+        Self { parts: Vec::new() }
+    }
+
+    /// Add a text field to the form.
+    pub fn text(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.parts.push(Part {
+            name: name.into(),
+            filename: None,
+            content_type: None,
+            content: PartContent::Text(value.into()),
+        });
+        self
+    }
+
+    /// Add a file to the form, streaming its contents from `reader`.
+    pub fn stream(
+        mut self,
+        name: impl Into<String>,
+        filename: impl Into<String>,
+        content_type: impl Into<String>,
+        reader: impl AsyncRead + 'static,
+    ) -> Self {
+        self.parts.push(Part {
+            name: name.into(),
+            filename: Some(filename.into()),
+            content_type: Some(content_type.into()),
+            content: PartContent::Reader(Box::pin(reader)),
+        });
+        self
+    }
+
+    /// Convert the form into the value of its `Content-Type` header, and its
+    /// body.
+    pub(crate) fn into_body(self) -> (String, Body) {
+        let boundary = self.boundary();
+        let content_type = format!("multipart/form-data; boundary={boundary}");
+
+        // Every field is framed by a header ahead of it and a line break after
+        // it, with a closing delimiter after the last one.
+        let mut len = Some(0);
+        let mut readers: VecDeque<Pin<Box<dyn AsyncRead>>> = VecDeque::new();
+        for part in self.parts {
+            let header = part.header(&boundary).into_bytes();
+            let content: Pin<Box<dyn AsyncRead>> = match part.content {
+                PartContent::Text(text) => {
+                    len = len.map(|len| len + header.len() + text.len() + 2);
+                    Box::pin(Cursor::new(text.into_bytes()))
+                }
+                PartContent::Reader(reader) => {
+                    len = None;
+                    reader
+                }
+            };
+            readers.push_back(Box::pin(Cursor::new(header)));
+            readers.push_back(content);
+            readers.push_back(Box::pin(Cursor::new(b"\r\n")));
+        }
+        let closing = format!("--{boundary}--\r\n").into_bytes();
+        len = len.map(|len| len + closing.len());
+        readers.push_back(Box::pin(Cursor::new(closing)));
+
+        let reader = MultipartReader { readers };
+        (
+            content_type,
+            Body::from_reader(reader, len.map(|len| len as u64)),
+        )
+    }
+
+    /// Generate a boundary which doesn't occur in any of the text fields.
+    ///
+    /// Streamed contents can't be checked ahead of time, but with 128 random
+    /// bits in the boundary a collision is not a practical concern.
+    fn boundary(&self) -> String {
+        loop {
+            let boundary = format!(
+                "wasi-http-client-{:016x}{:016x}",
+                wasi::random::random::get_random_u64(),
+                wasi::random::random::get_random_u64()
+            );
+            let collides = self.parts.iter().any(|part| match &part.content {
+                PartContent::Text(text) => text.contains(&boundary),
+                PartContent::Reader(_) => false,
+            });
+            if !collides {
+                return boundary;
+            }
+        }
+    }
+}
+
+impl Part {
+    /// The delimiter and headers which precede the part.
+    fn header(&self, boundary: &str) -> String {
+        let mut header = format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"{}\"",
+            escape(&self.name)
+        );
+        if let Some(filename) = &self.filename {
+            header.push_str(&format!("; filename=\"{}\"", escape(filename)));
+        }
+        header.push_str("\r\n");
+        if let Some(content_type) = &self.content_type {
+            header.push_str(&format!("Content-Type: {content_type}\r\n"));
+        }
+        header.push_str("\r\n");
+        header
+    }
+}
+
+/// Escape a field name or filename for use in a quoted header parameter, the
+/// way browsers do.
+fn escape(value: &str) -> String {
+    value
+        .replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+impl Default for Multipart {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Multipart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Multipart")
+            .field("parts", &self.parts.len())
+            .finish()
+    }
+}
+
+/// Reads each of the readers to the end, one after the other.
+struct MultipartReader {
+    readers: VecDeque<Pin<Box<dyn AsyncRead>>>,
+}
+
+impl AsyncRead for MultipartReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        while let Some(reader) = self.readers.front_mut() {
+            match reader.as_mut().poll_read(cx, buf) {
+                Poll::Ready(Ok(0)) if !buf.is_empty() => {
+                    self.readers.pop_front();
+                }
+                other => return other,
+            }
+        }
+        Poll::Ready(Ok(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_lite::AsyncReadExt;
+
+    use super::*;
+
+    #[test]
+    fn text_part_header() {
+        let part = Part {
+            name: "title".to_owned(),
+            filename: None,
+            content_type: None,
+            content: PartContent::Text(String::new()),
+        };
+        let expected = "--b\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\n";
+        assert_eq!(part.header("b"), expected);
+    }
+
+    #[test]
+    fn file_part_header_escapes_filename() {
+        let part = Part {
+            name: "upload".to_owned(),
+            filename: Some("a\"b\r\n.txt".to_owned()),
+            content_type: Some("text/plain".to_owned()),
+            content: PartContent::Text(String::new()),
+        };
+        let expected = "--b\r\n\
+            Content-Disposition: form-data; name=\"upload\"; filename=\"a%22b%0D%0A.txt\"\r\n\
+            Content-Type: text/plain\r\n\r\n";
+        assert_eq!(part.header("b"), expected);
+    }
+
+    #[test]
+    fn reader_chains_parts() {
+        let readers: VecDeque<Pin<Box<dyn AsyncRead>>> = [&b"one, "[..], b"", b"two"]
+            .into_iter()
+            .map(|bytes| Box::pin(Cursor::new(bytes)) as Pin<Box<dyn AsyncRead>>)
+            .collect();
+        let mut reader = MultipartReader { readers };
+        let mut out = Vec::new();
+        wasi_async_runtime::block_on(|_| reader.read_to_end(&mut out)).unwrap();
+        assert_eq!(out, b"one, two");
+    }
+}
@@ -0,0 +1,16 @@
+/// How a request follows HTTP redirects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RedirectPolicy {
+    /// Don't follow redirects, and return the redirect response instead.
+    None,
+    /// Follow up to this many redirects in a row.
+    Limited(u32),
+}
+
+impl Default for RedirectPolicy {
+    /// Follow up to 10 redirects.
+    fn default() -> Self {
+        Self::Limited(10)
+    }
+}
@@ -1,47 +1,64 @@
-use wasi::{
-    http::types::{IncomingBody, IncomingResponse},
-    io::streams::{InputStream, StreamError},
-};
-use wasi_async_runtime::Reactor;
+use futures_lite::{AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use std::io;
+use std::pin::pin;
+use wasi::http::types::IncomingResponse;
+use wasi_async_runtime::{InputStream, OutputStream, Reactor};
 
-use crate::Headers;
-
-/// Stream 2kb chunks at a time
-const CHUNK_SIZE: u64 = 2048;
+use super::body_reader::{BodyReader, ContentEncoding};
+use super::{Events, Lines};
+use crate::{Headers, Mime, StatusCode, Trailers};
 
 /// An HTTP response
 #[derive(Debug)]
 pub struct Response {
-    bytes_read: u64,
-    content_length: u64,
+    status: u16,
     headers: Headers,
-    reactor: Reactor,
-
-    // IMPORTANT: the order of these fields here matters. `incoming_body` must
-    // be dropped before `body_stream`.
-    body_stream: InputStream,
-    _incoming_body: IncomingBody,
+    body: BodyReader,
+    max_body_size: Option<usize>,
 }
 
 impl Response {
+    /// Create a response with a body held in memory.
+    ///
+    /// This is meant for responses which don't come from the network, such
+    /// as those returned by a [`MockTransport`](crate::MockTransport).
+    pub fn new(status: StatusCode, headers: Headers, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status: status.as_u16(),
+            headers,
+            body: BodyReader::from_bytes(body.into()),
+            max_body_size: None,
+        }
+    }
+
+    /// Convert an incoming WASI response.
+    ///
+    /// If `decompress` is set, a gzip or deflate encoded body is decoded, and
+    /// the `Content-Encoding` and `Content-Length` headers are removed since
+    /// they no longer describe the body.
     pub(crate) fn try_from_incoming(
         incoming: IncomingResponse,
         reactor: Reactor,
+        decompress: bool,
     ) -> crate::Result<Self> {
-        let headers: Headers = incoming.headers().into();
-
-        let (_, content_length) = headers
-            .0
-            .iter()
-            .find(|(k, _)| k.to_lowercase() == "content-length")
-            .expect("no content-length found; violates HTTP/1.1");
-        let content_length = content_length
-            .get(0)
-            .expect("no value found for content-length; violates HTTP/1.1");
-        let content_length = String::from_utf8(content_length.clone())
-            .unwrap()
-            .parse::<u64>()
-            .unwrap();
+        let status = incoming.status();
+        let mut headers: Headers = incoming.headers().into();
+
+        let mut encoding = ContentEncoding::Identity;
+        if decompress {
+            let mut values = headers.get_all("content-encoding");
+            let value = match (values.next(), values.next()) {
+                (Some(value), None) => ContentEncoding::parse(value),
+                _ => None,
+            };
+            if let Some(value) = value {
+                encoding = value;
+            }
+        }
+        if encoding != ContentEncoding::Identity {
+            headers.remove("content-encoding");
+            headers.remove("content-length");
+        }
 
         // `body_stream` is a child of `incoming_body` which means we cannot
         // drop the parent before we drop the child
@@ -51,17 +68,36 @@ impl Response {
         let body_stream = incoming_body
             .stream()
             .expect("cannot call `stream` twice on an incoming body");
+        let body_stream = InputStream::new(body_stream, &reactor);
 
         Ok(Self {
-            bytes_read: 0,
+            status,
             headers,
-            content_length,
-            body_stream,
-            _incoming_body: incoming_body,
-            reactor,
+            body: BodyReader::new(body_stream, incoming_body, encoding, reactor),
+            max_body_size: None,
         })
     }
 
+    /// Set the largest body that may be collected into memory.
+    pub(crate) fn set_max_body_size(&mut self, max: Option<usize>) {
+        self.max_body_size = max;
+    }
+
+    /// Replace the body with an empty one.
+    pub(crate) fn clear_body(&mut self) {
+        self.body = BodyReader::from_bytes(Vec::new());
+    }
+
+    /// Get the HTTP status code.
+    pub fn status(&self) -> StatusCode {
+        StatusCode::from(self.status)
+    }
+
+    /// Get the raw HTTP status code.
+    pub(crate) fn status_code(&self) -> u16 {
+        self.status
+    }
+
     /// Get the HTTP headers from the impl
     pub fn headers(&self) -> &Headers {
         &self.headers
@@ -71,26 +107,337 @@ impl Response {
     pub fn headers_mut(&mut self) -> &mut Headers {
         &mut self.headers
     }
+
+    /// Get the media type of the body, from the `Content-Type` header.
+    ///
+    /// Returns `None` if there is no such header, or it can't be parsed.
+    pub fn content_type(&self) -> Option<Mime> {
+        let value = std::str::from_utf8(self.headers.get("content-type")?).ok()?;
+        Mime::parse(value)
+    }
+
+    /// Whether the body is JSON, according to its `Content-Type`.
+    ///
+    /// See [`Mime::is_json`].
+    pub fn is_json(&self) -> bool {
+        self.content_type().is_some_and(|mime| mime.is_json())
+    }
+
+    /// Whether the body is text, according to its `Content-Type`.
+    ///
+    /// See [`Mime::is_text`].
+    pub fn is_text(&self) -> bool {
+        self.content_type().is_some_and(|mime| mime.is_text())
+    }
+
+    /// Convert the response into a reader over its body.
+    ///
+    /// The reader implements `AsyncRead` and `AsyncBufRead`, so large bodies
+    /// can be processed incrementally rather than collected in memory.
+    /// Trailers are only available once the body has been read to the end.
+    pub fn into_body_reader(self) -> BodyReader {
+        self.body
+    }
+
+    /// Read the body as a stream of lines, such as for newline-delimited
+    /// JSON.
+    ///
+    /// Lines are split on `\n` and yielded without their line break, which
+    /// may be either `\n` or `\r\n`. A final line without a line break is
+    /// yielded too. A line which isn't valid UTF-8 is yielded as an error,
+    /// after which the next lines can still be read.
+    pub fn lines(self) -> Lines {
+        Lines::new(self.body)
+    }
+
+    /// Parse the body as a `text/event-stream` of server-sent events.
+    ///
+    /// Events are yielded as they arrive, once the blank line ending them
+    /// has been received. The stream ends with the body; an event which
+    /// wasn't finished by then is dropped.
+    pub fn events(self) -> Events {
+        Events::new(self.body)
+    }
+
+    /// Wait for the trailers of the response.
+    ///
+    /// See [`BodyReader::trailers`]: the body has to be read to the end
+    /// before the trailers can be read.
+    pub async fn trailers(self) -> crate::Result<Option<Trailers>> {
+        self.body.trailers().await
+    }
+
+    /// Read the whole body into memory.
+    ///
+    /// Fails with [`Error::BodyTooLarge`](crate::Error::BodyTooLarge) if the
+    /// body exceeds the request's
+    /// [maximum body size](crate::Request::set_max_body_size). The body is
+    /// dropped in that case, which closes the connection.
+    pub async fn bytes(mut self) -> crate::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        let Some(max) = self.max_body_size else {
+            self.body.read_to_end(&mut bytes).await?;
+            return Ok(bytes);
+        };
+
+        // Don't bother reading a body which announces it is too large
+        let len = self.headers.get("content-length");
+        let len = len.and_then(|len| std::str::from_utf8(len).ok()?.trim().parse::<u64>().ok());
+        if len.is_some_and(|len| len > max as u64) {
+            return Err(crate::Error::BodyTooLarge(max));
+        }
+
+        loop {
+            let chunk = self.body.fill_buf().await?;
+            if chunk.is_empty() {
+                return Ok(bytes);
+            }
+            if bytes.len() + chunk.len() > max {
+                return Err(crate::Error::BodyTooLarge(max));
+            }
+            bytes.extend_from_slice(chunk);
+            let len = chunk.len();
+            AsyncBufReadExt::consume(&mut self.body, len);
+        }
+    }
+
+    /// Read the whole body and decode it as text.
+    ///
+    /// The body is decoded according to the `charset` of its `Content-Type`,
+    /// defaulting to UTF-8. Besides UTF-8, `iso-8859-1` (also known as
+    /// `latin1`) and `us-ascii` are supported. Fails with
+    /// [`Error::Body`](crate::Error::Body) for other charsets, or if the body
+    /// isn't valid in its charset.
+    pub async fn text(self) -> crate::Result<String> {
+        let charset = self
+            .content_type()
+            .and_then(|mime| mime.charset().map(str::to_ascii_lowercase));
+        let mut bytes = self.bytes().await?;
+        let invalid =
+            |msg: String| crate::Error::Body(io::Error::new(io::ErrorKind::InvalidData, msg));
+        match charset.as_deref() {
+            None | Some("utf-8" | "utf8") => {
+                if bytes.starts_with(b"\xef\xbb\xbf") {
+                    bytes.drain(..3);
+                }
+                String::from_utf8(bytes)
+                    .map_err(|err| invalid(format!("the body isn't valid UTF-8: {err}")))
+            }
+            Some("iso-8859-1" | "latin1" | "l1" | "iso_8859-1") => {
+                Ok(bytes.iter().map(|&b| char::from(b)).collect())
+            }
+            Some("us-ascii" | "ascii") if bytes.is_ascii() => {
+                // NOTE: ASCII is valid UTF-8
+                Ok(String::from_utf8(bytes).unwrap())
+            }
+            Some("us-ascii" | "ascii") => Err(invalid("the body isn't valid ASCII".to_owned())),
+            Some(charset) => Err(invalid(format!("the `{charset}` charset is not supported"))),
+        }
+    }
+
+    /// Stream the body into `writer`, returning the number of bytes written.
+    ///
+    /// The body is handed to the writer in the chunks it is received in, so
+    /// it is never held in memory in full. The writer is flushed once the
+    /// whole body has been written. Errors reading the body are returned as
+    /// [`Error::Body`](crate::Error::Body), and errors writing to `writer` as
+    /// [`Error::Write`](crate::Error::Write).
+    pub async fn copy_to(mut self, writer: impl AsyncWrite) -> crate::Result<u64> {
+        let mut writer = pin!(writer);
+        let mut written = 0;
+        loop {
+            let chunk = self.body.fill_buf().await?;
+            if chunk.is_empty() {
+                break;
+            }
+            writer.write_all(chunk).await.map_err(crate::Error::Write)?;
+            let len = chunk.len();
+            AsyncBufReadExt::consume(&mut self.body, len);
+            written += len as u64;
+        }
+        writer.flush().await.map_err(crate::Error::Write)?;
+        Ok(written)
+    }
+
+    /// Stream the body into a WASI output stream, returning the number of
+    /// bytes written.
+    ///
+    /// If the body isn't decoded, its bytes are spliced into `writer` by the
+    /// host without passing through the guest. Otherwise this behaves like
+    /// [`copy_to`](Self::copy_to). Since a splice both reads and writes, its
+    /// errors are returned as [`Error::Write`](crate::Error::Write).
+    pub async fn splice_to(mut self, writer: &mut OutputStream) -> crate::Result<u64> {
+        match self.body.identity_stream() {
+            Some(stream) => wasi_async_runtime::splice(stream, writer)
+                .await
+                .map_err(crate::Error::Write),
+            None => self.copy_to(writer).await,
+        }
+    }
+
+    /// Read the whole body and deserialize it from JSON.
+    ///
+    /// If the body isn't valid JSON for `T`, the error describes what went
+    /// wrong and where.
+    #[cfg(feature = "json")]
+    pub async fn json<T: serde::de::DeserializeOwned>(self) -> crate::Result<T> {
+        let bytes = self.bytes().await?;
+        serde_json::from_slice(&bytes)
+            .map_err(|err| crate::Error::Body(io::Error::new(io::ErrorKind::InvalidData, err)))
+    }
 }
 
 impl async_iterator::Iterator for Response {
-    type Item = Result<Vec<u8>, StreamError>;
+    type Item = io::Result<Vec<u8>>;
 
     async fn next(&mut self) -> Option<Self::Item> {
-        // Calculate how many bytes we can read
-        let remaining = self.content_length - self.bytes_read;
-        let len = remaining.min(CHUNK_SIZE);
-        if len == 0 {
-            return None;
-        }
+        // Hand out whatever the body has buffered, until it reports the end
+        let chunk = match self.body.fill_buf().await {
+            Ok([]) => return None,
+            Ok(chunk) => chunk.to_vec(),
+            Err(err) => return Some(Err(err)),
+        };
+        AsyncBufReadExt::consume(&mut self.body, chunk.len());
+        Some(Ok(chunk))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_lite::StreamExt;
+
+    use super::*;
+
+    #[test]
+    fn body_reader_reads_lines() {
+        let res = Response::new(StatusCode::Ok, Headers::new(), "one\ntwo\r\nthree");
+        let lines: Vec<_> = wasi_async_runtime::block_on(|_| async {
+            res.into_body_reader()
+                .lines()
+                .map(Result::unwrap)
+                .collect()
+                .await
+        });
+        assert_eq!(lines, ["one", "two", "three"]);
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn json_deserializes_body() {
+        let res = Response::new(
+            StatusCode::Ok,
+            Headers::new(),
+            r#"{"name":"ferris","legs":8}"#,
+        );
+        let value: serde_json::Value = wasi_async_runtime::block_on(|_| res.json()).unwrap();
+        assert_eq!(value["name"], "ferris");
+        assert_eq!(value["legs"], 8);
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn json_reports_truncated_body() {
+        let res = Response::new(StatusCode::Ok, Headers::new(), r#"{"name":"fer"#);
+        let result = wasi_async_runtime::block_on(|_| res.json::<serde_json::Value>());
+        let crate::Error::Body(err) = result.unwrap_err() else {
+            panic!("expected a body error");
+        };
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("EOF"), "{err}");
+    }
 
-        // Wait for an event to be ready
-        let pollable = self.body_stream.subscribe();
-        self.reactor.wait_for(pollable).await;
+    #[test]
+    fn in_memory_body_has_no_trailers() {
+        let res = Response::new(StatusCode::Ok, Headers::new(), "body");
+        let trailers = wasi_async_runtime::block_on(|_| async {
+            let mut reader = res.into_body_reader();
+            let mut body = Vec::new();
+            reader.read_to_end(&mut body).await.unwrap();
+            assert_eq!(body, b"body");
+            reader.trailers().await
+        });
+        assert_eq!(trailers.unwrap(), None);
+    }
+
+    #[test]
+    fn trailers_require_reading_the_body() {
+        let res = Response::new(StatusCode::Ok, Headers::new(), "body");
+        let err = wasi_async_runtime::block_on(|_| res.trailers()).unwrap_err();
+        assert!(matches!(err, crate::Error::Body(_)));
+    }
+
+    #[test]
+    fn copy_to_streams_whole_body() {
+        let body: Vec<u8> = (0..2 * 1024 * 1024).map(|i| i as u8).collect();
+        let res = Response::new(StatusCode::Ok, Headers::new(), body.clone());
+        let mut out = Vec::new();
+        let copied = wasi_async_runtime::block_on(|_| res.copy_to(&mut out)).unwrap();
+        assert_eq!(copied, body.len() as u64);
+        assert_eq!(out, body);
+    }
+
+    #[test]
+    fn bytes_rejects_body_over_max_size() {
+        let mut res = Response::new(StatusCode::Ok, Headers::new(), vec![0; 2 * 1024 * 1024]);
+        res.set_max_body_size(Some(1024 * 1024));
+        let err = wasi_async_runtime::block_on(|_| res.bytes()).unwrap_err();
+        assert!(matches!(err, crate::Error::BodyTooLarge(max) if max == 1024 * 1024));
+    }
+
+    #[test]
+    fn bytes_rejects_announced_length_over_max_size() {
+        let mut headers = Headers::new();
+        headers.append("content-length", b"2048".to_vec());
+        let mut res = Response::new(StatusCode::Ok, headers, "short");
+        res.set_max_body_size(Some(1024));
+        let err = wasi_async_runtime::block_on(|_| res.bytes()).unwrap_err();
+        assert!(matches!(err, crate::Error::BodyTooLarge(1024)));
+    }
+
+    #[test]
+    fn bytes_accepts_body_at_max_size() {
+        let mut res = Response::new(StatusCode::Ok, Headers::new(), vec![1; 1024]);
+        res.set_max_body_size(Some(1024));
+        let body = wasi_async_runtime::block_on(|_| res.bytes()).unwrap();
+        assert_eq!(body.len(), 1024);
+    }
+
+    /// A response with the given `Content-Type`.
+    fn with_content_type(content_type: &str, body: impl Into<Vec<u8>>) -> Response {
+        let mut headers = Headers::new();
+        headers.append("content-type", content_type.as_bytes().to_vec());
+        Response::new(StatusCode::Ok, headers, body.into())
+    }
+
+    #[test]
+    fn text_decodes_charset() {
+        let res = with_content_type("text/plain; charset=ISO-8859-1", b"caf\xe9".to_vec());
+        let text = wasi_async_runtime::block_on(|_| res.text()).unwrap();
+        assert_eq!(text, "caf\u{e9}");
+
+        let res = with_content_type("text/plain", b"\xef\xbb\xbfcaf\xc3\xa9".to_vec());
+        let text = wasi_async_runtime::block_on(|_| res.text()).unwrap();
+        assert_eq!(text, "caf\u{e9}");
+    }
+
+    #[test]
+    fn text_rejects_invalid_body() {
+        let res = with_content_type("text/plain; charset=us-ascii", b"caf\xe9".to_vec());
+        let err = wasi_async_runtime::block_on(|_| res.text()).unwrap_err();
+        assert!(matches!(err, crate::Error::Body(_)));
+
+        let res = with_content_type("text/plain; charset=shift_jis", "text");
+        let err = wasi_async_runtime::block_on(|_| res.text()).unwrap_err();
+        assert!(err.to_string().contains("shift_jis"), "{err}");
+    }
 
-        // Read the bytes from the body stream
-        let buf = self.body_stream.read(len);
-        self.bytes_read += len;
-        Some(buf)
+    #[test]
+    fn cleared_body_reads_empty() {
+        let mut headers = Headers::new();
+        headers.set("content-length", "4");
+        let mut res = Response::new(StatusCode::Ok, headers, "body");
+        res.clear_body();
+        let body = wasi_async_runtime::block_on(|_| res.bytes()).unwrap();
+        assert!(body.is_empty());
     }
 }
@@ -0,0 +1,172 @@
+use super::{Error, Method};
+use std::time::Duration;
+
+/// How a request is retried after a transient failure.
+///
+/// Between attempts the client waits according to an exponential backoff
+/// schedule: the first retry waits `base`, and every following retry waits
+/// twice as long as the one before, up to `max`. With jitter enabled, each
+/// wait is randomly shortened by up to half, so that many clients failing at
+/// once don't all retry in lockstep.
+///
+/// Only requests with idempotent methods (`GET`, `HEAD`, `PUT` and `DELETE`)
+/// are retried, unless [`RetryPolicy::set_retry_non_idempotent`] is set.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    statuses: Vec<u16>,
+    errors: fn(&Error) -> bool,
+    base: Duration,
+    max: Duration,
+    jitter: bool,
+    retry_non_idempotent: bool,
+}
+
+impl RetryPolicy {
+    /// Create a policy which sends a request at most `max_attempts` times,
+    /// including the first attempt.
+    ///
+    /// By default `429`, `502`, `503` and `504` responses and
+    /// connection-level errors are retried, with a backoff from 100
+    /// milliseconds up to 10 seconds, with jitter.
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            statuses: vec![429, 502, 503, 504],
+            errors: is_transient,
+            base: Duration::from_millis(100),
+            max: Duration::from_secs(10),
+            jitter: true,
+            retry_non_idempotent: false,
+        }
+    }
+
+    /// Get the maximum number of times a request is sent.
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Set which response status codes are retried.
+    pub fn set_statuses(&mut self, statuses: impl IntoIterator<Item = u16>) {
+        self.statuses = statuses.into_iter().collect();
+    }
+
+    /// Get which response status codes are retried.
+    pub fn statuses(&self) -> &[u16] {
+        &self.statuses
+    }
+
+    /// Set which errors are retried.
+    ///
+    /// By default only errors which happen while connecting to the server or
+    /// waiting on it are retried.
+    pub fn set_errors(&mut self, errors: fn(&Error) -> bool) {
+        self.errors = errors;
+    }
+
+    /// Set the backoff schedule between attempts.
+    pub fn set_backoff(&mut self, base: Duration, max: Duration) {
+        self.base = base;
+        self.max = max;
+    }
+
+    /// Set whether the wait between attempts is randomized.
+    pub fn set_jitter(&mut self, jitter: bool) {
+        self.jitter = jitter;
+    }
+
+    /// Set whether requests with non-idempotent methods such as `POST` are
+    /// retried too.
+    ///
+    /// Retrying these may cause the server to act on the request twice.
+    pub fn set_retry_non_idempotent(&mut self, retry: bool) {
+        self.retry_non_idempotent = retry;
+    }
+
+    /// Whether requests with this method may be retried.
+    pub(crate) fn allows(&self, method: &Method) -> bool {
+        self.retry_non_idempotent
+            || matches!(
+                method,
+                Method::Get | Method::Head | Method::Put | Method::Delete
+            )
+    }
+
+    /// Whether a response with this status code should be retried.
+    pub(crate) fn retries_status(&self, status: u16) -> bool {
+        self.statuses.contains(&status)
+    }
+
+    /// Whether this error should be retried.
+    pub(crate) fn retries_error(&self, err: &Error) -> bool {
+        (self.errors)(err)
+    }
+
+    /// How long to wait before the retry following the `attempt`th attempt,
+    /// counting from zero.
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .base
+            .checked_mul(2u32.saturating_pow(attempt))
+            .unwrap_or(self.max)
+            .min(self.max);
+        if !self.jitter {
+            return delay;
+        }
+        let half = delay / 2;
+        let nanos = u64::try_from(half.as_nanos()).unwrap_or(u64::MAX);
+        let offset = match nanos {
+            0 => 0,
+            nanos => wasi::random::random::get_random_u64() % nanos,
+        };
+        half + Duration::from_nanos(offset)
+    }
+}
+
+/// Errors which happen while connecting to or waiting on the server, which
+/// are likely to go away if the request is tried again.
+fn is_transient(err: &Error) -> bool {
+    matches!(err, Error::Connect(_) | Error::Timeout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let mut policy = RetryPolicy::new(5);
+        policy.set_jitter(false);
+        policy.set_backoff(Duration::from_millis(100), Duration::from_millis(500));
+        let delays: Vec<_> = (0..5).map(|attempt| policy.backoff(attempt)).collect();
+        let millis = [100, 200, 400, 500, 500].map(Duration::from_millis);
+        assert_eq!(delays, millis);
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn retries_transient_statuses_only() {
+        let policy = RetryPolicy::new(3);
+        assert!(policy.retries_status(503));
+        assert!(policy.retries_status(429));
+        assert!(!policy.retries_status(400));
+        assert!(!policy.retries_status(200));
+    }
+
+    #[test]
+    fn retries_transient_errors_only() {
+        let policy = RetryPolicy::new(3);
+        assert!(policy.retries_error(&Error::Timeout));
+        assert!(!policy.retries_error(&Error::TooManyRedirects));
+    }
+
+    #[test]
+    fn only_idempotent_methods_by_default() {
+        let mut policy = RetryPolicy::new(3);
+        assert!(policy.allows(&Method::Get));
+        assert!(policy.allows(&Method::Delete));
+        assert!(!policy.allows(&Method::Post));
+        policy.set_retry_non_idempotent(true);
+        assert!(policy.allows(&Method::Post));
+    }
+}
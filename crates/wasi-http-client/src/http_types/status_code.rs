@@ -1,9 +1,11 @@
+use std::fmt;
+
 /// HTTP Status Codes
 ///
 /// See the [Status Code
 /// Registry](https://www.iana.org/assignments/http-status-codes/http-status-codes.xhtml)
 /// for more information
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u16)]
 #[allow(missing_docs)]
 #[non_exhaustive]
@@ -55,6 +57,101 @@ pub enum StatusCode {
     Other(u16),
 }
 
+impl StatusCode {
+    /// Get the numeric value of the status code.
+    pub fn as_u16(&self) -> u16 {
+        u16::from(*self)
+    }
+
+    /// Whether the status code is in the `1xx` range.
+    pub fn is_informational(&self) -> bool {
+        (100..200).contains(&self.as_u16())
+    }
+
+    /// Whether the status code is in the `2xx` range.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.as_u16())
+    }
+
+    /// Whether the status code is in the `3xx` range.
+    pub fn is_redirect(&self) -> bool {
+        (300..400).contains(&self.as_u16())
+    }
+
+    /// Whether the status code is in the `4xx` range.
+    pub fn is_client_error(&self) -> bool {
+        (400..500).contains(&self.as_u16())
+    }
+
+    /// Whether the status code is in the `5xx` range.
+    pub fn is_server_error(&self) -> bool {
+        (500..600).contains(&self.as_u16())
+    }
+
+    /// Get the reason phrase registered for the status code, if it has one.
+    pub fn canonical_reason(&self) -> Option<&'static str> {
+        let reason = match self.as_u16() {
+            100 => "Continue",
+            101 => "Switching Protocols",
+            200 => "OK",
+            201 => "Created",
+            202 => "Accepted",
+            203 => "Non-Authoritative Information",
+            204 => "No Content",
+            205 => "Reset Content",
+            206 => "Partial Content",
+            300 => "Multiple Choices",
+            301 => "Moved Permanently",
+            302 => "Found",
+            303 => "See Other",
+            304 => "Not Modified",
+            305 => "Use Proxy",
+            307 => "Temporary Redirect",
+            308 => "Permanent Redirect",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            402 => "Payment Required",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            406 => "Not Acceptable",
+            407 => "Proxy Authentication Required",
+            408 => "Request Timeout",
+            409 => "Conflict",
+            410 => "Gone",
+            411 => "Length Required",
+            412 => "Precondition Failed",
+            413 => "Content Too Large",
+            414 => "URI Too Long",
+            415 => "Unsupported Media Type",
+            416 => "Range Not Satisfiable",
+            417 => "Expectation Failed",
+            421 => "Misdirected Request",
+            422 => "Unprocessable Content",
+            426 => "Upgrade Required",
+            500 => "Internal Server Error",
+            501 => "Not Implemented",
+            502 => "Bad Gateway",
+            503 => "Service Unavailable",
+            504 => "Gateway Timeout",
+            505 => "HTTP Version Not Supported",
+            _ => return None,
+        };
+        Some(reason)
+    }
+}
+
+impl fmt::Display for StatusCode {
+    /// Formats the status code along with its reason phrase, such as
+    /// `404 Not Found`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.canonical_reason() {
+            Some(reason) => write!(f, "{} {reason}", self.as_u16()),
+            None => write!(f, "{}", self.as_u16()),
+        }
+    }
+}
+
 impl From<u16> for StatusCode {
     fn from(input: u16) -> Self {
         match input {
@@ -158,3 +255,50 @@ impl From<StatusCode> for u16 {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_boundary_codes() {
+        let class = |code: u16| {
+            let status = StatusCode::from(code);
+            [
+                status.is_informational(),
+                status.is_success(),
+                status.is_redirect(),
+                status.is_client_error(),
+                status.is_server_error(),
+            ]
+        };
+        assert_eq!(class(99), [false; 5]);
+        assert_eq!(class(100), [true, false, false, false, false]);
+        assert_eq!(class(199), [true, false, false, false, false]);
+        assert_eq!(class(200), [false, true, false, false, false]);
+        assert_eq!(class(299), [false, true, false, false, false]);
+        assert_eq!(class(300), [false, false, true, false, false]);
+        assert_eq!(class(399), [false, false, true, false, false]);
+        assert_eq!(class(400), [false, false, false, true, false]);
+        assert_eq!(class(499), [false, false, false, true, false]);
+        assert_eq!(class(500), [false, false, false, false, true]);
+        assert_eq!(class(599), [false, false, false, false, true]);
+        assert_eq!(class(600), [false; 5]);
+    }
+
+    #[test]
+    fn round_trips_every_code() {
+        for code in 0..1000 {
+            assert_eq!(StatusCode::from(code).as_u16(), code);
+        }
+        assert_eq!(StatusCode::from(404), StatusCode::NotFound);
+        assert_eq!(StatusCode::from(299), StatusCode::Other(299));
+    }
+
+    #[test]
+    fn displays_reason() {
+        assert_eq!(StatusCode::Ok.to_string(), "200 OK");
+        assert_eq!(StatusCode::from(299).to_string(), "299");
+        assert_eq!(StatusCode::from(299).canonical_reason(), None);
+    }
+}
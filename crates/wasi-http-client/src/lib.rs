@@ -11,37 +11,710 @@
 #![warn(missing_docs, future_incompatible, unreachable_pub)]
 
 pub use http_types::{
-    Error, FieldName, FieldValue, Fields, Headers, Method, Request, Response, Result, StatusCode,
-    Trailers,
+    Body, BodyReader, ChunkedDecoder, ChunkedEncoder, Cookie, CookieJar, Error, Event, Events,
+    FieldName, FieldValue, Fields, Headers, Lines, Method, Mime, Multipart, RedirectPolicy,
+    Request, Response, Result, RetryPolicy, StatusCode, Trailers,
 };
+pub use resolver::{Resolver, StaticResolver, WasiResolver};
+pub use transport::{MockTransport, Transport, WasiTransport};
 pub use url::Url;
 
-use wasi_async_runtime::Reactor;
+use std::time::Duration;
 
+use wasi_async_runtime::{race, timeout_at, Deadline, Either, Reactor};
+
+mod base64;
 mod http_types;
+mod resolver;
+mod transport;
+pub mod websocket;
 
 /// An HTTP client.
+///
+/// Requests are sent over a [`Transport`], which is a [`WasiTransport`]
+/// unless the client was created with [`Client::with_transport`].
+///
+/// # Connection reuse
+///
+/// `wasi:http` doesn't expose connections to guests: the host opens them,
+/// and decides whether to keep them alive and reuse them between requests.
+/// The pool settings of [`ClientBuilder`] are kept so code configuring them
+/// keeps working, but they currently have no effect.
+///
+/// For the same reason the client doesn't queue requests per connection.
+/// Concurrent requests are all handed to the host, which keeps them from
+/// interleaving on an HTTP/1.1 connection. Holding on to a response body
+/// doesn't keep a connection checked out of the client, though the host may
+/// not reuse that connection until the body has been read or dropped.
 #[derive(Debug)]
-pub struct Client {
+pub struct Client<T = WasiTransport, R = WasiResolver> {
     reactor: Reactor,
+    transport: T,
+    resolver: R,
+    cookie_jar: Option<CookieJar>,
+    max_headers: usize,
+    max_header_size: usize,
+    default_headers: Headers,
+    pool_max_idle_per_host: usize,
+    pool_idle_timeout: Option<Duration>,
 }
 
+/// The `User-Agent` sent unless the client is configured otherwise.
+const DEFAULT_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// The default limit on the number of response headers.
+const DEFAULT_MAX_HEADERS: usize = 100;
+
+/// The default limit on the total size of the response headers, in bytes.
+const DEFAULT_MAX_HEADER_SIZE: usize = 64 * 1024;
+
+/// The default time an idle connection may be kept alive for.
+const DEFAULT_POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
 impl Client {
     /// Create a new instance of `Client`
     pub fn new(reactor: Reactor) -> Self {
-        Self { reactor }
+        let transport = WasiTransport::new(reactor.clone());
+        Self::with_transport(reactor, transport)
+    }
+
+    /// Create a builder for configuring a client.
+    pub fn builder(reactor: Reactor) -> ClientBuilder {
+        ClientBuilder::new(reactor)
+    }
+}
+
+impl<T: Transport> Client<T> {
+    /// Create a client sending its requests over `transport`.
+    pub fn with_transport(reactor: Reactor, transport: T) -> Self {
+        let resolver = WasiResolver::new(reactor.clone());
+        Self::with_parts(reactor, transport, resolver)
+    }
+}
+
+impl<T: Transport, R: Resolver> Client<T, R> {
+    /// Create a client with the default configuration.
+    fn with_parts(reactor: Reactor, transport: T, resolver: R) -> Self {
+        Self {
+            reactor,
+            transport,
+            resolver,
+            cookie_jar: None,
+            max_headers: DEFAULT_MAX_HEADERS,
+            max_header_size: DEFAULT_MAX_HEADER_SIZE,
+            default_headers: default_headers(),
+            pool_max_idle_per_host: usize::MAX,
+            pool_idle_timeout: Some(DEFAULT_POOL_IDLE_TIMEOUT),
+        }
+    }
+
+    /// Get the transport the client sends its requests over.
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// Get the resolver the client looks up hosts with.
+    ///
+    /// See [`ClientBuilder::resolver`].
+    pub fn resolver(&self) -> &R {
+        &self.resolver
+    }
+
+    /// Open a WebSocket connection to a `ws` URL, looking up its host with
+    /// the client's resolver.
+    ///
+    /// See [`WebSocket::connect`](websocket::WebSocket::connect).
+    pub async fn connect_websocket(&self, url: &Url) -> std::io::Result<websocket::WebSocket> {
+        websocket::WebSocket::connect_with_resolver(&self.reactor, url, &self.resolver).await
+    }
+
+    /// Store cookies set by responses in `jar`, and send them along with
+    /// later requests they match.
+    ///
+    /// Cookies are only attached to requests which don't already have a
+    /// `Cookie` header.
+    pub fn set_cookie_jar(&mut self, jar: CookieJar) {
+        self.cookie_jar = Some(jar);
+    }
+
+    /// Get the cookie jar used by the client, if there is one.
+    pub fn cookie_jar(&self) -> Option<&CookieJar> {
+        self.cookie_jar.as_ref()
+    }
+
+    /// Get the headers sent with every request.
+    ///
+    /// Each default header is added to requests which don't set a header
+    /// with the same name themselves. By default this holds a `User-Agent`
+    /// naming this crate and its version.
+    pub fn default_headers(&self) -> &Headers {
+        &self.default_headers
+    }
+
+    /// Mutably get the headers sent with every request.
+    ///
+    /// See [`Client::default_headers`].
+    pub fn default_headers_mut(&mut self) -> &mut Headers {
+        &mut self.default_headers
+    }
+
+    /// Set the largest number of headers a response may have.
+    ///
+    /// Responses with more headers fail with
+    /// [`Error::HeadersTooLarge`]. Defaults to 100.
+    pub fn set_max_headers(&mut self, max: usize) {
+        self.max_headers = max;
+    }
+
+    /// Get the largest number of headers a response may have.
+    pub fn max_headers(&self) -> usize {
+        self.max_headers
+    }
+
+    /// Set the largest total size of the headers of a response, in bytes.
+    ///
+    /// The size of a header is the length of its name plus the length of
+    /// its value. Responses with larger headers fail with
+    /// [`Error::HeadersTooLarge`]. Defaults to 64 KiB.
+    pub fn set_max_header_size(&mut self, max: usize) {
+        self.max_header_size = max;
+    }
+
+    /// Get the largest total size of the headers of a response, in bytes.
+    pub fn max_header_size(&self) -> usize {
+        self.max_header_size
+    }
+
+    /// Get the largest number of idle connections to keep per host.
+    ///
+    /// See [`ClientBuilder::pool_max_idle_per_host`].
+    pub fn pool_max_idle_per_host(&self) -> usize {
+        self.pool_max_idle_per_host
+    }
+
+    /// Get how long an idle connection may be kept alive for.
+    ///
+    /// See [`ClientBuilder::pool_idle_timeout`].
+    pub fn pool_idle_timeout(&self) -> Option<Duration> {
+        self.pool_idle_timeout
+    }
+
+    /// Create a `GET` request to `url`, to be sent with [`Client::send`].
+    ///
+    /// Fails if `url` can't be parsed; see [`Request::parse`].
+    pub fn get(&self, url: &str) -> Result<Request> {
+        Request::parse(Method::Get, url)
+    }
+
+    /// Create a `POST` request to `url`, to be sent with [`Client::send`].
+    ///
+    /// Fails if `url` can't be parsed; see [`Request::parse`].
+    pub fn post(&self, url: &str) -> Result<Request> {
+        Request::parse(Method::Post, url)
+    }
+
+    /// Create a `PUT` request to `url`, to be sent with [`Client::send`].
+    ///
+    /// Fails if `url` can't be parsed; see [`Request::parse`].
+    pub fn put(&self, url: &str) -> Result<Request> {
+        Request::parse(Method::Put, url)
+    }
+
+    /// Create a `DELETE` request to `url`, to be sent with [`Client::send`].
+    ///
+    /// Fails if `url` can't be parsed; see [`Request::parse`].
+    pub fn delete(&self, url: &str) -> Result<Request> {
+        Request::parse(Method::Delete, url)
+    }
+
+    /// Create a `PATCH` request to `url`, to be sent with [`Client::send`].
+    ///
+    /// Fails if `url` can't be parsed; see [`Request::parse`].
+    pub fn patch(&self, url: &str) -> Result<Request> {
+        Request::parse(Method::Patch, url)
+    }
+
+    /// Create a `HEAD` request to `url`, to be sent with [`Client::send`].
+    ///
+    /// The response to a `HEAD` request always has an empty body, while
+    /// headers describing the body such as `Content-Length` are kept as they
+    /// were sent. Fails if `url` can't be parsed; see [`Request::parse`].
+    pub fn head(&self, url: &str) -> Result<Request> {
+        Request::parse(Method::Head, url)
     }
 
     /// Send an HTTP request.
-    pub async fn send(&self, req: Request) -> Result<Response> {
-        let wasi_req = req.into();
-        let res = wasi::http::outgoing_handler::handle(wasi_req, None).unwrap();
-        self.reactor.wait_for(res.subscribe()).await;
-
-        // NOTE: the first `unwrap` is to ensure readiness, the second `unwrap`
-        // is to trap if we try and get the response more than once. The final
-        // `?` is go raise the actual error if there is one.
-        let res = res.get().unwrap().unwrap()?;
-        Ok(Response::try_from_incoming(res, self.reactor.clone())?)
+    ///
+    /// Redirects are followed according to the request's
+    /// [`RedirectPolicy`].
+    pub async fn send(&self, mut req: Request) -> Result<Response> {
+        self.apply_default_headers(&mut req)?;
+        let Some(token) = req.cancellation_token().cloned() else {
+            return self.send_with_timeout(req).await;
+        };
+        match race(token.cancelled(), self.send_with_timeout(req)).await {
+            Either::Left(()) => Err(Error::Cancelled),
+            Either::Right(res) => res,
+        }
+    }
+
+    /// Send an HTTP request, bounded by the request's timeout and deadline.
+    async fn send_with_timeout(&self, req: Request) -> Result<Response> {
+        let deadline = match (req.timeout().map(Deadline::after), req.deadline()) {
+            (Some(timeout), Some(deadline)) => timeout.min(deadline),
+            (Some(deadline), None) | (None, Some(deadline)) => deadline,
+            (None, None) => return self.send_with_retries(req).await,
+        };
+
+        // Dropping the losing future releases all of its WASI resources, and
+        // deregisters their pollables from the reactor.
+        match timeout_at(&self.reactor, deadline, self.send_with_retries(req)).await {
+            Ok(res) => res,
+            Err(_) => Err(Error::Timeout),
+        }
+    }
+
+    /// Send an HTTP request, retrying it according to its [`RetryPolicy`].
+    async fn send_with_retries(&self, mut req: Request) -> Result<Response> {
+        let policy = match req.retry_policy() {
+            Some(policy) if policy.allows(req.method()) => policy.clone(),
+            _ => return self.send_following_redirects(req).await,
+        };
+        let mut attempt = 0;
+        loop {
+            let next = req.clone_without_body();
+            let body = req.body().try_clone();
+
+            let res = self.send_following_redirects(req).await;
+            attempt += 1;
+            let retry = match &res {
+                Ok(res) => policy.retries_status(res.status_code()),
+                Err(err) => policy.retries_error(err),
+            };
+            if !retry || attempt >= policy.max_attempts() {
+                return res;
+            }
+            let Some(body) = body else {
+                return Err(Error::Body(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "the request body can't be replayed, so the request can't be retried",
+                )));
+            };
+
+            self.reactor.sleep(policy.backoff(attempt - 1)).await;
+            req = next;
+            req.set_body(body);
+        }
+    }
+
+    /// Send an HTTP request, following redirects.
+    async fn send_following_redirects(&self, mut req: Request) -> Result<Response> {
+        let mut redirects = 0;
+        loop {
+            // Hold on to a copy of the request in case we need to follow a
+            // redirect, since sending it consumes the original.
+            let policy = req.redirect_policy();
+            let next = req.clone_without_body();
+            let body = req.body().try_clone();
+
+            let res = self.send_once(req).await?;
+            let Some(location) = redirect_location(&res, next.url()) else {
+                return Ok(res);
+            };
+            match policy {
+                RedirectPolicy::None => return Ok(res),
+                RedirectPolicy::Limited(max) if redirects >= max => {
+                    return Err(Error::TooManyRedirects)
+                }
+                RedirectPolicy::Limited(_) => {}
+            }
+            req = match next.into_redirect(res.status_code(), location, body) {
+                Some(next) => next,
+                None => return Ok(res),
+            };
+            redirects += 1;
+        }
+    }
+
+    /// Send a single HTTP request, without following redirects.
+    async fn send_once(&self, mut req: Request) -> Result<Response> {
+        let url = req.url().clone();
+        Request::validate_url(&url)?;
+        if let Some(jar) = &self.cookie_jar {
+            if !req.has_header("cookie") {
+                if let Some(cookies) = jar.header_for(&url) {
+                    req.set_header("cookie", cookies)?;
+                }
+            }
+        }
+
+        let max_body_size = req.max_body_size();
+        let head = *req.method() == Method::Head;
+        let mut res = self.transport.send(req).await?;
+        self.check_headers(res.headers())?;
+        if head {
+            // The headers of a HEAD response describe a body which is never
+            // sent, so there is nothing to read or limit
+            res.clear_body();
+        } else {
+            res.set_max_body_size(max_body_size);
+        }
+        if let Some(jar) = &self.cookie_jar {
+            jar.store(&url, res.headers().get_all("set-cookie"));
+        }
+        Ok(res)
+    }
+
+    /// Add the default headers the request doesn't set itself.
+    fn apply_default_headers(&self, req: &mut Request) -> Result<()> {
+        let missing: Vec<_> = self
+            .default_headers
+            .iter()
+            .filter(|(name, _)| !req.has_header(name))
+            .collect();
+        for (name, value) in missing {
+            req.append_header(name, value.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Check the headers of a response against the configured limits.
+    fn check_headers(&self, headers: &Headers) -> Result<()> {
+        if headers.len() > self.max_headers {
+            return Err(Error::HeadersTooLarge);
+        }
+        let mut size = 0usize;
+        for (name, value) in headers.iter() {
+            size = size.saturating_add(name.len() + value.len());
+            if size > self.max_header_size {
+                return Err(Error::HeadersTooLarge);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Configures and creates a [`Client`].
+///
+/// Created by [`Client::builder`].
+#[derive(Debug)]
+pub struct ClientBuilder<R = WasiResolver> {
+    reactor: Reactor,
+    resolver: R,
+    default_headers: Headers,
+    pool_max_idle_per_host: usize,
+    pool_idle_timeout: Option<Duration>,
+}
+
+impl ClientBuilder {
+    /// Create a builder with the default configuration.
+    fn new(reactor: Reactor) -> Self {
+        Self {
+            resolver: WasiResolver::new(reactor.clone()),
+            reactor,
+            default_headers: default_headers(),
+            pool_max_idle_per_host: usize::MAX,
+            pool_idle_timeout: Some(DEFAULT_POOL_IDLE_TIMEOUT),
+        }
+    }
+}
+
+impl<R: Resolver> ClientBuilder<R> {
+    /// Look up hosts with `resolver`.
+    ///
+    /// Requests sent through `wasi:http` are resolved by the host, so this
+    /// only applies to connections the client opens over raw sockets, such
+    /// as [`Client::connect_websocket`]. Defaults to a [`WasiResolver`].
+    pub fn resolver<R2: Resolver>(self, resolver: R2) -> ClientBuilder<R2> {
+        ClientBuilder {
+            reactor: self.reactor,
+            resolver,
+            default_headers: self.default_headers,
+            pool_max_idle_per_host: self.pool_max_idle_per_host,
+            pool_idle_timeout: self.pool_idle_timeout,
+        }
+    }
+
+    /// Set the `User-Agent` sent with every request.
+    ///
+    /// Defaults to the name and version of this crate.
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.default_headers.set("user-agent", user_agent);
+        self
+    }
+
+    /// Don't send a `User-Agent` unless a request sets one itself.
+    pub fn no_user_agent(mut self) -> Self {
+        self.default_headers.remove("user-agent");
+        self
+    }
+
+    /// Send a header with every request which doesn't set a header with the
+    /// same name itself, replacing any earlier default with this name.
+    pub fn default_header(mut self, name: &str, value: impl Into<FieldValue>) -> Self {
+        self.default_headers.set(name.to_owned(), value);
+        self
+    }
+
+    /// Set the largest number of idle connections to keep per host.
+    ///
+    /// Defaults to no limit. The host owns the connections, so this has no
+    /// effect yet; see [Connection reuse](Client#connection-reuse).
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = max;
+        self
+    }
+
+    /// Set how long an idle connection may be kept alive for, or `None` to
+    /// keep it alive indefinitely.
+    ///
+    /// Defaults to 90 seconds. The host owns the connections, so this has no
+    /// effect yet; see [Connection reuse](Client#connection-reuse).
+    pub fn pool_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.pool_idle_timeout = timeout;
+        self
+    }
+
+    /// Create the client.
+    pub fn build(self) -> Client<WasiTransport, R> {
+        let transport = WasiTransport::new(self.reactor.clone());
+        self.build_with_transport(transport)
+    }
+
+    /// Create a client sending its requests over `transport`.
+    pub fn build_with_transport<T: Transport>(self, transport: T) -> Client<T, R> {
+        let mut client = Client::with_parts(self.reactor, transport, self.resolver);
+        client.default_headers = self.default_headers;
+        client.pool_max_idle_per_host = self.pool_max_idle_per_host;
+        client.pool_idle_timeout = self.pool_idle_timeout;
+        client
+    }
+}
+
+/// The default headers a client starts out with.
+fn default_headers() -> Headers {
+    let mut headers = Headers::new();
+    headers.set("user-agent", DEFAULT_USER_AGENT);
+    headers
+}
+
+/// Get the URL a redirect response points to, resolved against the URL of
+/// the request.
+///
+/// Returns `None` if the response isn't a redirect, or has no valid
+/// `Location` header.
+fn redirect_location(res: &Response, base: &Url) -> Option<Url> {
+    if !matches!(res.status_code(), 301 | 302 | 303 | 307 | 308) {
+        return None;
+    }
+    let location = std::str::from_utf8(res.headers().get("location")?).ok()?;
+    base.join(location).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redirect(status: StatusCode, location: &str) -> Response {
+        let mut headers = Headers::new();
+        headers.set("location", location);
+        Response::new(status, headers, Vec::new())
+    }
+
+    #[test]
+    fn redirect_location_resolves_against_request() {
+        let base = Url::parse("https://example.com/a/b?q").unwrap();
+        let res = redirect(StatusCode::Found, "../c");
+        let location = redirect_location(&res, &base).unwrap();
+        assert_eq!(location.as_str(), "https://example.com/c");
+
+        let res = redirect(StatusCode::PermanentRedirect, "http://other.test/");
+        let location = redirect_location(&res, &base).unwrap();
+        assert_eq!(location.as_str(), "http://other.test/");
+    }
+
+    #[test]
+    fn redirect_location_ignores_other_responses() {
+        let base = Url::parse("https://example.com/").unwrap();
+        // Only redirect statuses are followed, even with a `Location`
+        assert!(redirect_location(&redirect(StatusCode::Created, "/new"), &base).is_none());
+        assert!(redirect_location(&redirect(StatusCode::NotModified, "/"), &base).is_none());
+        let res = Response::new(StatusCode::Found, Headers::new(), Vec::new());
+        assert!(redirect_location(&res, &base).is_none());
+    }
+
+    #[test]
+    fn builder_sets_default_headers() {
+        wasi_async_runtime::block_on(|reactor| async move {
+            let client =
+                Client::builder(reactor.clone()).build_with_transport(MockTransport::new());
+            let headers = client.default_headers();
+            assert_eq!(
+                headers.get("user-agent").unwrap(),
+                DEFAULT_USER_AGENT.as_bytes()
+            );
+            assert_eq!(client.pool_max_idle_per_host(), usize::MAX);
+            assert_eq!(client.pool_idle_timeout(), Some(DEFAULT_POOL_IDLE_TIMEOUT));
+
+            let client = Client::builder(reactor)
+                .user_agent("custom/1.0")
+                .default_header("accept", "application/json")
+                .default_header("accept", "text/plain")
+                .pool_max_idle_per_host(4)
+                .pool_idle_timeout(None)
+                .build_with_transport(MockTransport::new());
+            let headers = client.default_headers();
+            assert_eq!(headers.get("user-agent").unwrap(), b"custom/1.0");
+            assert_eq!(headers.get_all("accept").count(), 1);
+            assert_eq!(headers.get("accept").unwrap(), b"text/plain");
+            assert_eq!(client.pool_max_idle_per_host(), 4);
+            assert_eq!(client.pool_idle_timeout(), None);
+        });
+    }
+
+    #[test]
+    fn builder_can_drop_user_agent() {
+        wasi_async_runtime::block_on(|reactor| async move {
+            let client = Client::builder(reactor)
+                .no_user_agent()
+                .build_with_transport(MockTransport::new());
+            assert!(client.default_headers().get("user-agent").is_none());
+        });
+    }
+
+    #[test]
+    fn send_follows_redirects_through_transport() {
+        let mock = MockTransport::new();
+        mock.on(
+            |req| req.url().path() == "/old",
+            |_| Ok(redirect(StatusCode::Found, "/new")),
+        );
+        mock.on(
+            |req| req.url().path() == "/new",
+            |req| {
+                // The redirected POST is turned into a GET without a body
+                assert!(req.body().is_empty());
+                assert!(req.header("content-length").is_none());
+                Ok(Response::new(StatusCode::Ok, Headers::new(), "done"))
+            },
+        );
+
+        let body = wasi_async_runtime::block_on(|reactor| {
+            let mock = mock.clone();
+            async move {
+                let client = Client::with_transport(reactor, mock);
+                let mut req = client.post("https://example.com/old").unwrap();
+                req.set_body(Body::from_bytes("form"));
+                client.send(req).await.unwrap().bytes().await.unwrap()
+            }
+        });
+        assert_eq!(body, b"done");
+        let requests: Vec<_> = mock
+            .requests()
+            .into_iter()
+            .map(|(method, url)| (method, url.to_string()))
+            .collect();
+        assert_eq!(
+            requests,
+            [
+                (Method::Post, "https://example.com/old".to_owned()),
+                (Method::Get, "https://example.com/new".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn send_stops_after_redirect_limit() {
+        let mock = MockTransport::new();
+        mock.on(|_| true, |_| Ok(redirect(StatusCode::Found, "/loop")));
+        let err = wasi_async_runtime::block_on(|reactor| async move {
+            let client = Client::with_transport(reactor, mock);
+            let mut req = client.get("https://example.com/loop").unwrap();
+            req.set_redirect_policy(RedirectPolicy::Limited(3));
+            client.send(req).await.unwrap_err()
+        });
+        assert!(matches!(err, Error::TooManyRedirects), "{err:?}");
+    }
+
+    #[test]
+    fn check_headers_enforces_limits() {
+        wasi_async_runtime::block_on(|reactor| async move {
+            let mut client = Client::builder(reactor).build_with_transport(MockTransport::new());
+            client.set_max_headers(2);
+            client.set_max_header_size(10);
+
+            // Two headers of `1 + 4` bytes each are right at the limits
+            let mut headers = Headers::new();
+            headers.append("a", "1234");
+            headers.append("b", "1234");
+            assert!(client.check_headers(&headers).is_ok());
+
+            let mut too_large = headers.clone();
+            too_large.set("b", "12345");
+            let err = client.check_headers(&too_large).unwrap_err();
+            assert!(matches!(err, Error::HeadersTooLarge), "{err:?}");
+
+            let mut too_many = headers;
+            too_many.append("c", "");
+            let err = client.check_headers(&too_many).unwrap_err();
+            assert!(matches!(err, Error::HeadersTooLarge), "{err:?}");
+        });
+    }
+
+    #[test]
+    fn send_rejects_oversized_response_headers() {
+        let mock = MockTransport::new();
+        mock.on(
+            |_| true,
+            |_| {
+                let mut headers = Headers::new();
+                headers.set("x-large", vec![b'a'; 100]);
+                Ok(Response::new(StatusCode::Ok, headers, Vec::new()))
+            },
+        );
+        let err = wasi_async_runtime::block_on(|reactor| async move {
+            let mut client = Client::with_transport(reactor, mock);
+            client.set_max_header_size(64);
+            let req = client.get("https://example.com/").unwrap();
+            client.send(req).await.unwrap_err()
+        });
+        assert!(matches!(err, Error::HeadersTooLarge), "{err:?}");
+    }
+
+    #[test]
+    fn builder_sets_resolver() {
+        let resolver = StaticResolver::new();
+        resolver.insert("example.com", [std::net::Ipv4Addr::LOCALHOST.into()]);
+        let addrs = wasi_async_runtime::block_on(|reactor| async move {
+            let client = Client::builder(reactor)
+                .resolver(resolver)
+                .build_with_transport(MockTransport::new());
+            client.resolver().resolve("example.com").await.unwrap()
+        });
+        assert_eq!(addrs, [std::net::IpAddr::from([127, 0, 0, 1])]);
+    }
+
+    #[test]
+    fn head_response_has_empty_body() {
+        let mock = MockTransport::new();
+        mock.on(
+            |req| *req.method() == Method::Head,
+            |_| {
+                let mut headers = Headers::new();
+                headers.set("content-length", "1234");
+                Ok(Response::new(StatusCode::Ok, headers, Vec::new()))
+            },
+        );
+        let (content_length, body) = wasi_async_runtime::block_on(|reactor| async move {
+            let client = Client::with_transport(reactor, mock);
+            let mut req = client.head("https://example.com/file").unwrap();
+            // The announced length would be too large to read otherwise
+            req.set_max_body_size(16);
+            let res = client.send(req).await.unwrap();
+            let content_length = res.headers().get("content-length").cloned();
+            (content_length, res.bytes().await.unwrap())
+        });
+        assert_eq!(content_length.unwrap(), b"1234");
+        assert!(body.is_empty());
     }
 }
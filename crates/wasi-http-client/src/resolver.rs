@@ -0,0 +1,119 @@
+//! Looking up the addresses of hosts.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::net::IpAddr;
+use std::rc::Rc;
+use wasi_async_runtime::Reactor;
+
+/// Resolves host names to IP addresses.
+///
+/// Used for connections made over raw sockets, such as
+/// [`WebSocket`](crate::websocket::WebSocket)s. Requests sent through
+/// `wasi:http` are resolved by the host instead. By default names are looked
+/// up through `wasi:sockets/ip-name-lookup` by a [`WasiResolver`]; a
+/// [`StaticResolver`] can pin names to fixed addresses instead. A client is
+/// given a resolver through [`ClientBuilder::resolver`](crate::ClientBuilder::resolver).
+pub trait Resolver {
+    /// Resolve `host` to its IP addresses, in order of preference.
+    fn resolve(&self, host: &str) -> impl Future<Output = io::Result<Vec<IpAddr>>>;
+}
+
+/// Resolves names through `wasi:sockets/ip-name-lookup`.
+///
+/// See [`wasi_async_runtime::resolve`].
+#[derive(Debug, Clone)]
+pub struct WasiResolver {
+    reactor: Reactor,
+}
+
+impl WasiResolver {
+    /// Create a new resolver looking up names on `reactor`.
+    pub fn new(reactor: Reactor) -> Self {
+        Self { reactor }
+    }
+}
+
+impl Resolver for WasiResolver {
+    async fn resolve(&self, host: &str) -> io::Result<Vec<IpAddr>> {
+        wasi_async_runtime::resolve(&self.reactor, host).await
+    }
+}
+
+/// Resolves names to a fixed set of addresses.
+///
+/// Names are matched case-insensitively. Names which were never added fail
+/// to resolve with [`io::ErrorKind::NotFound`], except for IP addresses,
+/// which resolve to themselves. Clones share the same set of names.
+#[derive(Debug, Clone, Default)]
+pub struct StaticResolver {
+    names: Rc<RefCell<HashMap<String, Vec<IpAddr>>>>,
+}
+
+impl StaticResolver {
+    /// Create a new resolver without any names.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolve `host` to `addrs`, replacing any addresses it had before.
+    pub fn insert(&self, host: &str, addrs: impl IntoIterator<Item = IpAddr>) {
+        let addrs = addrs.into_iter().collect();
+        self.names
+            .borrow_mut()
+            .insert(host.to_ascii_lowercase(), addrs);
+    }
+
+    /// Stop resolving `host`.
+    pub fn remove(&self, host: &str) {
+        self.names.borrow_mut().remove(&host.to_ascii_lowercase());
+    }
+}
+
+impl Resolver for StaticResolver {
+    async fn resolve(&self, host: &str) -> io::Result<Vec<IpAddr>> {
+        if let Ok(addr) = host.parse::<IpAddr>() {
+            return Ok(vec![addr]);
+        }
+        match self.names.borrow().get(&host.to_ascii_lowercase()) {
+            Some(addrs) if !addrs.is_empty() => Ok(addrs.clone()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no addresses found for `{host}`"),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    #[test]
+    fn static_resolver_returns_mapped_addresses() {
+        let resolver = StaticResolver::new();
+        resolver.insert("Example.com", [IpAddr::V4(Ipv4Addr::LOCALHOST)]);
+        let addrs = wasi_async_runtime::block_on(|_| resolver.resolve("example.COM"));
+        assert_eq!(addrs.unwrap(), [IpAddr::V4(Ipv4Addr::LOCALHOST)]);
+
+        // IP addresses resolve to themselves
+        let addrs = wasi_async_runtime::block_on(|_| resolver.resolve("::1"));
+        assert_eq!(addrs.unwrap(), ["::1".parse::<IpAddr>().unwrap()]);
+    }
+
+    #[test]
+    fn static_resolver_rejects_unknown_hosts() {
+        let resolver = StaticResolver::new();
+        resolver.insert("example.com", [IpAddr::V4(Ipv4Addr::LOCALHOST)]);
+        resolver.insert("empty.test", []);
+        resolver.clone().remove("example.com");
+        for host in ["example.com", "empty.test", "unknown.test"] {
+            let err = wasi_async_runtime::block_on(|_| resolver.resolve(host)).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::NotFound, "{host}");
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! The transports requests are sent over.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::future::Future;
+use std::rc::Rc;
+use std::time::Duration;
+use wasi::http::types::ErrorCode;
+use wasi_async_runtime::Reactor;
+
+use crate::{Method, Request, Response, Result};
+
+/// How long to wait for the server to reject a request sent with
+/// `Expect: 100-continue`, before sending its body anyway.
+const EXPECT_CONTINUE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Sends a single request and receives its response.
+///
+/// The [`Client`](crate::Client) handles everything on top of that, such as
+/// redirects, retries, timeouts and cookies. By default requests go out over
+/// `wasi:http` through a [`WasiTransport`]; a [`MockTransport`] can take its
+/// place to test code using the client without making any network requests.
+pub trait Transport {
+    /// Send a request, without following redirects.
+    fn send(&self, req: Request) -> impl Future<Output = Result<Response>>;
+}
+
+/// Sends requests through `wasi:http/outgoing-handler`.
+#[derive(Debug, Clone)]
+pub struct WasiTransport {
+    reactor: Reactor,
+}
+
+impl WasiTransport {
+    /// Create a new transport sending requests on `reactor`.
+    pub fn new(reactor: Reactor) -> Self {
+        Self { reactor }
+    }
+}
+
+impl Transport for WasiTransport {
+    async fn send(&self, req: Request) -> Result<Response> {
+        let options = req.options()?;
+        // HEAD responses have no body to decode, and their `Content-Length`
+        // should be left alone
+        let decompress = req.auto_decompress() && *req.method() != Method::Head;
+        let expect_continue = req.expect_continue();
+        let content_length = req.content_length();
+        let (wasi_req, body) = req.into_outgoing()?;
+        let wasi_body = wasi_req.body().unwrap();
+        let res = wasi::http::outgoing_handler::handle(wasi_req, options)?;
+
+        // `wasi:http` doesn't expose interim responses, so the best we can do
+        // is give the server a moment to reject the request. If a final
+        // response arrives in that time we never send the body: dropping the
+        // outgoing body without finishing it aborts the upload.
+        let rejected = expect_continue
+            && !body.is_empty()
+            && self
+                .reactor
+                .wait_for_with_timeout(res.subscribe(), EXPECT_CONTINUE_TIMEOUT)
+                .await;
+        if rejected {
+            drop(wasi_body);
+        } else {
+            // Write the body out before waiting for the response
+            body.send(wasi_body, content_length, &self.reactor).await?;
+            self.reactor.wait_for(res.subscribe()).await;
+        }
+
+        // NOTE: the first `unwrap` is to ensure readiness, the second `unwrap`
+        // is to trap if we try and get the response more than once. The final
+        // `?` is go raise the actual error if there is one.
+        let res = res.get().unwrap().unwrap()?;
+        Response::try_from_incoming(res, self.reactor.clone(), decompress)
+    }
+}
+
+type Matcher = Rc<dyn Fn(&Request) -> bool>;
+type Responder = Rc<dyn Fn(&Request) -> Result<Response>>;
+
+/// A transport answering requests with scripted responses.
+///
+/// Each request is answered by the first rule whose matcher accepts it.
+/// Requests no rule matches fail with [`Error::Connect`](crate::Error::Connect),
+/// as if the server couldn't be reached. Clones of a mock share their rules
+/// and the requests they've seen.
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    inner: Rc<RefCell<MockInner>>,
+}
+
+#[derive(Default)]
+struct MockInner {
+    rules: Vec<(Matcher, Responder)>,
+    requests: Vec<(crate::Method, url::Url)>,
+}
+
+impl MockTransport {
+    /// Create a mock transport without any rules.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer requests accepted by `matcher` with the result of `respond`.
+    pub fn on(
+        &self,
+        matcher: impl Fn(&Request) -> bool + 'static,
+        respond: impl Fn(&Request) -> Result<Response> + 'static,
+    ) {
+        let rule: (Matcher, Responder) = (Rc::new(matcher), Rc::new(respond));
+        self.inner.borrow_mut().rules.push(rule);
+    }
+
+    /// Get the method and URL of every request sent so far, in order.
+    pub fn requests(&self) -> Vec<(crate::Method, url::Url)> {
+        self.inner.borrow().requests.clone()
+    }
+}
+
+impl Transport for MockTransport {
+    async fn send(&self, req: Request) -> Result<Response> {
+        let respond = {
+            let mut inner = self.inner.borrow_mut();
+            inner
+                .requests
+                .push((req.method().clone(), req.url().clone()));
+            let rule = inner.rules.iter().find(|(matcher, _)| matcher(&req));
+            rule.map(|(_, respond)| respond.clone())
+        };
+        // The mock isn't borrowed anymore, so the responder may use it too
+        match respond {
+            Some(respond) => respond(&req),
+            None => Err(ErrorCode::DestinationNotFound.into()),
+        }
+    }
+}
+
+impl fmt::Debug for MockTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.inner.borrow();
+        f.debug_struct("MockTransport")
+            .field("rules", &inner.rules.len())
+            .field("requests", &inner.requests)
+            .finish()
+    }
+}
@@ -0,0 +1,573 @@
+//! WebSocket client connections.
+//!
+//! `wasi:http` has no support for upgrading a connection, so WebSockets are
+//! spoken directly over a [`TcpStream`]: [`WebSocket::connect`] performs the
+//! HTTP/1.1 upgrade handshake itself, after which messages are framed as
+//! described in [RFC 6455](https://www.rfc-editor.org/rfc/rfc6455). Secure
+//! `wss` connections aren't supported, since there is no TLS over raw
+//! sockets.
+
+use futures_lite::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt};
+use std::io;
+use std::net::SocketAddr;
+use url::Url;
+use wasi_async_runtime::{Reactor, TcpStream};
+
+use crate::{base64, Resolver, WasiResolver};
+
+/// The GUID appended to the key when computing `Sec-WebSocket-Accept`.
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// The largest message we accept, so a peer can't make us buffer without
+/// bound.
+const MAX_MESSAGE_LEN: usize = 16 * 1024 * 1024;
+
+/// The longest line we accept in the handshake response.
+const MAX_LINE_LEN: usize = 8 * 1024;
+
+/// A message sent over a WebSocket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    /// A UTF-8 text message.
+    Text(String),
+    /// A binary message.
+    Binary(Vec<u8>),
+    /// A ping, which the peer should answer with a pong carrying the same
+    /// payload.
+    Ping(Vec<u8>),
+    /// The answer to a ping.
+    Pong(Vec<u8>),
+    /// The closing handshake, with an optional status code and reason.
+    Close(Option<(u16, String)>),
+}
+
+/// A WebSocket connection.
+#[derive(Debug)]
+pub struct WebSocket {
+    stream: TcpStream,
+    /// Whether we've sent a close frame, after which nothing else may be sent.
+    closed: bool,
+    /// The opcode and payload received so far of a fragmented message.
+    partial: Option<(u8, Vec<u8>)>,
+}
+
+impl WebSocket {
+    /// Open a WebSocket connection to a `ws` URL.
+    ///
+    /// Connects to the host of the URL and performs the upgrade handshake.
+    /// Fails with [`io::ErrorKind::Unsupported`] for `wss` URLs, and with
+    /// [`io::ErrorKind::InvalidData`] if the server doesn't accept the
+    /// upgrade.
+    pub async fn connect(reactor: &Reactor, url: &Url) -> io::Result<Self> {
+        Self::connect_with_resolver(reactor, url, &WasiResolver::new(reactor.clone())).await
+    }
+
+    /// Open a WebSocket connection to a `ws` URL, looking up its host with
+    /// `resolver`.
+    ///
+    /// See [`WebSocket::connect`].
+    pub async fn connect_with_resolver(
+        reactor: &Reactor,
+        url: &Url,
+        resolver: &impl Resolver,
+    ) -> io::Result<Self> {
+        match url.scheme() {
+            "ws" => {}
+            "wss" => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "secure WebSocket connections are not supported",
+                ))
+            }
+            scheme => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("`{scheme}` is not a WebSocket URL scheme"),
+                ))
+            }
+        }
+        let Some(host) = url.host_str() else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the URL has no host",
+            ));
+        };
+        let port = url.port().unwrap_or(80);
+        // IPv6 hosts are bracketed in URLs, but not in addresses
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let addrs: Vec<_> = resolver
+            .resolve(host)
+            .await?
+            .into_iter()
+            .map(|ip| SocketAddr::new(ip, port))
+            .collect();
+        let stream = TcpStream::connect_addrs(reactor, &addrs).await?;
+        Self::handshake(stream, url).await
+    }
+
+    /// Perform the upgrade handshake for `url` over an open connection.
+    pub async fn handshake(mut stream: TcpStream, url: &Url) -> io::Result<Self> {
+        let key = base64::encode(&random_bytes::<16>());
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{host}:{port}"),
+            (Some(host), None) => host.to_owned(),
+            (None, _) => String::new(),
+        };
+        let target = match url.query() {
+            Some(query) => format!("{}?{query}", url.path()),
+            None => url.path().to_owned(),
+        };
+        let request = format!(
+            "GET {target} HTTP/1.1\r\n\
+             Host: {host}\r\n\
+             Upgrade: websocket\r\n\
+             Connection: Upgrade\r\n\
+             Sec-WebSocket-Key: {key}\r\n\
+             Sec-WebSocket-Version: 13\r\n\r\n"
+        );
+        stream.write_all(request.as_bytes()).await?;
+        stream.flush().await?;
+
+        let status = read_line(&mut stream).await?;
+        let mut parts = status.splitn(3, ' ');
+        if !parts.next().is_some_and(|v| v.starts_with("HTTP/1.")) || parts.next() != Some("101") {
+            return Err(invalid(format!(
+                "the server didn't accept the WebSocket upgrade: `{status}`"
+            )));
+        }
+
+        let (mut upgrade, mut connection, mut accept) = (false, false, None);
+        loop {
+            let line = read_line(&mut stream).await?;
+            if line.is_empty() {
+                break;
+            }
+            let Some((name, value)) = line.split_once(':') else {
+                return Err(invalid("invalid header in the handshake response"));
+            };
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("upgrade") {
+                upgrade = value.eq_ignore_ascii_case("websocket");
+            } else if name.eq_ignore_ascii_case("connection") {
+                connection = value
+                    .split(',')
+                    .any(|token| token.trim().eq_ignore_ascii_case("upgrade"));
+            } else if name.eq_ignore_ascii_case("sec-websocket-accept") {
+                accept = Some(value.to_owned());
+            }
+        }
+        if !upgrade || !connection {
+            return Err(invalid("the server didn't upgrade the connection"));
+        }
+        if accept.as_deref() != Some(accept_key(&key).as_str()) {
+            return Err(invalid("the server sent an invalid `Sec-WebSocket-Accept`"));
+        }
+
+        Ok(Self {
+            stream,
+            closed: false,
+            partial: None,
+        })
+    }
+
+    /// Send a message.
+    ///
+    /// Fails with [`io::ErrorKind::BrokenPipe`] once a close message has
+    /// been sent.
+    pub async fn send(&mut self, message: Message) -> io::Result<()> {
+        if self.closed {
+            return Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "the WebSocket has been closed",
+            ));
+        }
+        let (opcode, payload) = match message {
+            Message::Text(text) => (OPCODE_TEXT, text.into_bytes()),
+            Message::Binary(bytes) => (OPCODE_BINARY, bytes),
+            Message::Ping(bytes) => (OPCODE_PING, bytes),
+            Message::Pong(bytes) => (OPCODE_PONG, bytes),
+            Message::Close(close) => {
+                let mut payload = Vec::new();
+                if let Some((code, reason)) = close {
+                    payload.extend_from_slice(&code.to_be_bytes());
+                    payload.extend_from_slice(reason.as_bytes());
+                }
+                (OPCODE_CLOSE, payload)
+            }
+        };
+        if opcode >= OPCODE_CLOSE && payload.len() > 125 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the payload of a control message can be at most 125 bytes",
+            ));
+        }
+        if opcode == OPCODE_CLOSE {
+            self.closed = true;
+        }
+        let frame = encode_frame(opcode, &payload, random_bytes());
+        self.stream.write_all(&frame).await?;
+        self.stream.flush().await
+    }
+
+    /// Receive the next message.
+    ///
+    /// Fragmented messages are joined back together. Control messages may
+    /// arrive between the fragments of a message; they are returned as they
+    /// arrive, and the fragmented message is completed by a later call. Pings
+    /// are answered with a pong automatically, and are returned as well. Once
+    /// a close message is received, one is sent back if we haven't sent one
+    /// yet.
+    pub async fn recv(&mut self) -> io::Result<Message> {
+        loop {
+            let frame = self.read_frame().await?;
+            match frame.opcode {
+                OPCODE_PING => {
+                    if !self.closed {
+                        self.send(Message::Pong(frame.payload.clone())).await?;
+                    }
+                    return Ok(Message::Ping(frame.payload));
+                }
+                OPCODE_PONG => return Ok(Message::Pong(frame.payload)),
+                OPCODE_CLOSE => {
+                    let close = match frame.payload.as_slice() {
+                        [] => None,
+                        [hi, lo, reason @ ..] => {
+                            let reason = String::from_utf8(reason.to_vec())
+                                .map_err(|_| invalid("the close reason isn't valid UTF-8"))?;
+                            Some((u16::from_be_bytes([*hi, *lo]), reason))
+                        }
+                        [_] => return Err(invalid("received a malformed close frame")),
+                    };
+                    if !self.closed {
+                        let code = close.as_ref().map(|(code, _)| (*code, String::new()));
+                        self.send(Message::Close(code)).await?;
+                    }
+                    return Ok(Message::Close(close));
+                }
+                _ => {
+                    if let Some(message) = reassemble(&mut self.partial, frame)? {
+                        return Ok(message);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Send a close message, with an optional status code and reason.
+    ///
+    /// The connection should be read from until the server's close message
+    /// is received, after which it can be dropped.
+    pub async fn close(&mut self, close: Option<(u16, String)>) -> io::Result<()> {
+        self.send(Message::Close(close)).await
+    }
+
+    /// Unwrap the underlying connection.
+    pub fn into_inner(self) -> TcpStream {
+        self.stream
+    }
+
+    async fn read_frame(&mut self) -> io::Result<Frame> {
+        let frame = decode_frame(&mut self.stream).await?;
+        if frame.masked {
+            return Err(invalid("received a masked frame from the server"));
+        }
+        Ok(frame)
+    }
+}
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xa;
+
+/// A single frame read off the connection.
+#[derive(Debug)]
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    /// Whether the payload was masked. It has been unmasked already.
+    masked: bool,
+    payload: Vec<u8>,
+}
+
+/// Read a single frame, unmasking its payload if it was masked.
+async fn decode_frame(reader: &mut (impl AsyncRead + Unpin)) -> io::Result<Frame> {
+    let mut head = [0; 2];
+    reader.read_exact(&mut head).await?;
+    let fin = head[0] & 0x80 != 0;
+    if head[0] & 0x70 != 0 {
+        return Err(invalid("received a frame with reserved bits set"));
+    }
+    let opcode = head[0] & 0x0f;
+    let masked = head[1] & 0x80 != 0;
+    let len = match head[1] & 0x7f {
+        126 => {
+            let mut len = [0; 2];
+            reader.read_exact(&mut len).await?;
+            u64::from(u16::from_be_bytes(len))
+        }
+        127 => {
+            let mut len = [0; 8];
+            reader.read_exact(&mut len).await?;
+            u64::from_be_bytes(len)
+        }
+        len => u64::from(len),
+    };
+    if opcode >= OPCODE_CLOSE && (!fin || len > 125) {
+        return Err(invalid("received a malformed control frame"));
+    }
+    if len > MAX_MESSAGE_LEN as u64 {
+        return Err(invalid("the WebSocket message is too large"));
+    }
+    let mut mask = [0; 4];
+    if masked {
+        reader.read_exact(&mut mask).await?;
+    }
+    let mut payload = vec![0; len as usize];
+    reader.read_exact(&mut payload).await?;
+    if masked {
+        for (byte, m) in payload.iter_mut().zip(mask.iter().cycle()) {
+            *byte ^= m;
+        }
+    }
+    Ok(Frame {
+        fin,
+        opcode,
+        masked,
+        payload,
+    })
+}
+
+/// Add a data frame to the fragmented message in `partial`, returning the
+/// message once its final frame has been added.
+fn reassemble(partial: &mut Option<(u8, Vec<u8>)>, frame: Frame) -> io::Result<Option<Message>> {
+    match frame.opcode {
+        OPCODE_CONTINUATION => {
+            let Some((_, payload)) = partial else {
+                return Err(invalid("received a continuation frame outside a message"));
+            };
+            if payload.len() + frame.payload.len() > MAX_MESSAGE_LEN {
+                return Err(invalid("the WebSocket message is too large"));
+            }
+            payload.extend_from_slice(&frame.payload);
+        }
+        OPCODE_TEXT | OPCODE_BINARY => {
+            if partial.is_some() {
+                return Err(invalid("received a new message inside a fragmented one"));
+            }
+            *partial = Some((frame.opcode, frame.payload));
+        }
+        _ => return Err(invalid("received a frame with an unknown opcode")),
+    }
+    if !frame.fin {
+        return Ok(None);
+    }
+
+    // NOTE: data frames always start or continue a message
+    let (opcode, payload) = partial.take().unwrap();
+    match opcode {
+        OPCODE_TEXT => String::from_utf8(payload)
+            .map(|text| Some(Message::Text(text)))
+            .map_err(|_| invalid("received a text message which isn't valid UTF-8")),
+        _ => Ok(Some(Message::Binary(payload))),
+    }
+}
+
+/// Encode a single, final frame as sent by a client, masking the payload
+/// with `mask`.
+fn encode_frame(opcode: u8, payload: &[u8], mask: [u8; 4]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + 14);
+    frame.push(0x80 | opcode);
+    match payload.len() {
+        len @ 0..=125 => frame.push(0x80 | len as u8),
+        len @ 126..=0xffff => {
+            frame.push(0x80 | 126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(0x80 | 127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(&mask);
+    frame.extend(payload.iter().zip(mask.iter().cycle()).map(|(b, m)| b ^ m));
+    frame
+}
+
+/// Read a line of the handshake response, without its line break.
+async fn read_line(stream: &mut TcpStream) -> io::Result<String> {
+    let mut line = Vec::new();
+    loop {
+        let available = stream.fill_buf().await?;
+        if available.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the connection closed during the WebSocket handshake",
+            ));
+        }
+        let (len, done) = match available.iter().position(|&b| b == b'\n') {
+            Some(i) => (i + 1, true),
+            None => (available.len(), false),
+        };
+        line.extend_from_slice(&available[..len]);
+        stream.consume(len);
+        if line.len() > MAX_LINE_LEN {
+            return Err(invalid("line in the handshake response is too long"));
+        }
+        if done {
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            return String::from_utf8(line)
+                .map_err(|_| invalid("the handshake response isn't valid UTF-8"));
+        }
+    }
+}
+
+/// Compute the `Sec-WebSocket-Accept` the server should answer `key` with.
+fn accept_key(key: &str) -> String {
+    base64::encode(&sha1(format!("{key}{ACCEPT_GUID}").as_bytes()))
+}
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0; N];
+    for chunk in bytes.chunks_mut(8) {
+        let random = wasi::random::random::get_random_u64().to_le_bytes();
+        chunk.copy_from_slice(&random[..chunk.len()]);
+    }
+    bytes
+}
+
+fn invalid(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+/// Compute the SHA-1 digest of `data`.
+///
+/// SHA-1 is only used for the handshake, as RFC 6455 requires; it isn't
+/// relied on for security.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a827999),
+                20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (h, x) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(x);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (chunk, word) in digest.chunks_mut(4).zip(h) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(fin: bool, opcode: u8, payload: &[u8]) -> Frame {
+        Frame {
+            fin,
+            opcode,
+            masked: false,
+            payload: payload.to_vec(),
+        }
+    }
+
+    #[test]
+    fn accept_key_matches_rfc_example() {
+        // From section 1.3 of RFC 6455
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn encoded_frames_decode() {
+        for len in [0, 5, 125, 126, 200, 0xffff, 0x10000] {
+            let payload: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let encoded = encode_frame(OPCODE_BINARY, &payload, [1, 2, 3, 4]);
+            let frame = wasi_async_runtime::block_on(|_| async {
+                decode_frame(&mut encoded.as_slice()).await.unwrap()
+            });
+            assert!(frame.fin);
+            assert!(frame.masked);
+            assert_eq!(frame.opcode, OPCODE_BINARY);
+            assert_eq!(frame.payload, payload);
+        }
+    }
+
+    #[test]
+    fn decode_rejects_fragmented_control_frames() {
+        let encoded = [OPCODE_PING, 0];
+        let err = wasi_async_runtime::block_on(|_| async {
+            decode_frame(&mut encoded.as_slice()).await.unwrap_err()
+        });
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn reassembles_fragments() {
+        let mut partial = None;
+        let first = reassemble(&mut partial, frame(false, OPCODE_TEXT, b"hel")).unwrap();
+        assert_eq!(first, None);
+        let message = reassemble(&mut partial, frame(true, OPCODE_CONTINUATION, b"lo"));
+        assert_eq!(message.unwrap(), Some(Message::Text("hello".to_owned())));
+        assert!(partial.is_none());
+    }
+
+    #[test]
+    fn rejects_stray_continuation() {
+        let mut partial = None;
+        let frame = frame(true, OPCODE_CONTINUATION, b"lo");
+        assert!(reassemble(&mut partial, frame).is_err());
+    }
+
+    #[test]
+    fn rejects_interleaved_messages() {
+        let mut partial = None;
+        reassemble(&mut partial, frame(false, OPCODE_TEXT, b"a")).unwrap();
+        let frame = frame(true, OPCODE_BINARY, b"b");
+        assert!(reassemble(&mut partial, frame).is_err());
+    }
+}